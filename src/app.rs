@@ -1,18 +1,26 @@
 use crate::{
     error::{Error, Result},
-    filename, fs,
-    schema::Schema,
+    filename::{self, limit::Profile, value},
+    fs::{
+        self,
+        journal::{self, Entry},
+    },
+    query,
+    schema::{Category, Keyword, KeywordRef, Schema, ValueKind},
     State,
 };
 use eframe::egui::{
     self,
+    collapsing_header::CollapsingState,
     panel::{Side, TopBottomSide},
-    Button, Color32, FontFamily, Key, Label,
+    Button, Checkbox, Color32, FontFamily, Key, Label, Modifiers, Rect, Sense, Stroke,
 };
+use egui_extras::DatePickerButton;
 use rand::{rngs::ThreadRng, thread_rng};
 use std::{
+    collections::{BTreeSet, HashMap},
     fs::File,
-    io::Read,
+    io::{self, Read},
     path::{Path, PathBuf},
     result::Result as StdResult,
     sync::Arc,
@@ -28,24 +36,40 @@ pub struct AppConfig {
     pub file_id: String,
     pub zoom: f32,
     pub ui_state: State,
+    /// text categories as they're being typed, by category. What's typed isn't always usable until it's finished.
+    pub text_inputs: HashMap<usize, String>,
     pub files: Vec<PathBuf>,
+    /// every file in the working directory. `files` is the subset matching the current filters.
+    pub all_files: Vec<PathBuf>,
+    pub query: String,
+    /// hides files that are already tagged so work can pick up where it left off
+    pub skip_tagged: bool,
+    /// number of files in the working directory whose names match the schema
+    pub tagged: usize,
+    pub filter_error: Option<String>,
+    /// length limits of the file system new names must fit in
+    pub profile: Profile,
+    /// renames undone this session that can be reapplied
+    pub redo: Vec<Entry>,
+    /// indices into `files` picked in the filmstrip to all be tagged with `ui_state` at once
+    pub selected: BTreeSet<usize>,
+    /// files that couldn't be renamed in the last batch, with the reason
+    pub batch_errors: Vec<String>,
     pub rng: ThreadRng,
 }
 
+/// width and height of the images in the filmstrip
+const THUMBNAIL_SIZE: f32 = 80.0;
+
 impl AppConfig {
-    pub fn run_with(schema: Schema, working_dir: PathBuf) -> Result<()> {
+    pub fn run_with(
+        schema: Schema,
+        working_dir: PathBuf,
+        resume: bool,
+        profile: Profile,
+    ) -> Result<()> {
         info!("Reading working directory");
-        let files: Vec<PathBuf> = fs::collect_filenames(&working_dir)?
-            .into_iter()
-            .filter(|path| {
-                // since this string representation is only used to rule out certain files, it's safe to use even in cross-platform builds
-                let filename = path
-                    .file_name()
-                    .map_or(String::new(), |fname| fname.to_string_lossy().to_string());
-                // skip dotfiles and our schema file
-                !filename.starts_with('.') && filename != "schema.q"
-            })
-            .collect();
+        let files = fs::collect_taggable(&working_dir)?;
 
         // UI must display the first image. Exit if there's nothing in the directory.
         if files.is_empty() {
@@ -60,14 +84,28 @@ impl AppConfig {
             ctx: Arc::new(egui::Context::default()),
             schema,
             ui_state,
+            text_inputs: HashMap::new(),
             working_dir,
             active: 0,
             file_id: "".to_string(),
             zoom: 1.0,
+            all_files: files.clone(),
             files,
+            query: String::new(),
+            skip_tagged: resume,
+            tagged: 0,
+            filter_error: None,
+            profile,
+            redo: vec![],
+            selected: BTreeSet::new(),
+            batch_errors: vec![],
             rng,
         };
-        app.gen_id();
+        app.count_tagged();
+        if resume {
+            app.apply_filters();
+        }
+        app.read_active();
 
         info!("Building the UI");
         let options = eframe::NativeOptions {
@@ -104,19 +142,20 @@ impl AppConfig {
     }
 
     fn clear_state(&mut self) {
-        self.ui_state = to_empty_state(&self.schema)
+        self.ui_state = to_empty_state(&self.schema);
+        self.text_inputs.clear();
     }
 
     fn next(&mut self) {
         self.active = self.inc_file_index_by(1, self.active);
         self.zoom = 1.0;
-        self.gen_id();
+        self.read_active();
     }
 
     fn prev(&mut self) {
         self.active = self.dec_file_index_by(1, self.active);
         self.zoom = 1.0;
-        self.gen_id();
+        self.read_active();
     }
 
     fn inc_file_index_by(&self, n: usize, current: usize) -> usize {
@@ -131,21 +170,36 @@ impl AppConfig {
         self.file_id = filename::gen_rand_id(&mut self.rng);
     }
 
-    fn mk_filename(&self) -> StdResult<String, String> {
-        match filename::generate(&self.schema, &self.ui_state) {
-            Ok(name) => {
-                let id = self.file_id.clone();
-                let delim = self.schema.delim.clone();
-                let ext = match self.active_file().extension() {
-                    Some(ext) => format!(".{}", ext.to_string_lossy()),
-                    None => String::new(),
-                };
-                Ok(format!("{id}{delim}{name}{ext}"))
+    /// picks up the id and keywords of a file that is already tagged so re-tagging edits them in place.
+    /// untagged files keep the current selections and get a fresh id.
+    fn read_active(&mut self) {
+        match filename::parse::parse_path(&self.schema, self.active_file()) {
+            Ok(parsed) => {
+                self.file_id = parsed.id;
+                self.ui_state = parsed.state;
+                self.text_inputs.clear();
             }
-            Err(e) => Err(e.to_string()),
+            Err(_) => self.gen_id(),
         }
     }
 
+    fn mk_filename(&self) -> StdResult<String, String> {
+        let ext = self
+            .active_file()
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_string());
+        filename::to_path(
+            &self.schema,
+            &self.working_dir,
+            &self.file_id,
+            &self.ui_state,
+            ext.as_deref(),
+            &self.profile,
+        )
+        .map(|path| fs::name_of(&path))
+        .map_err(|e| e.to_string())
+    }
+
     fn to_uri(path: &Path) -> String {
         let mut uri = "bytes://".to_string();
         uri.push_str(&path.to_string_lossy());
@@ -156,62 +210,317 @@ impl AppConfig {
         &self.files[self.active]
     }
 
-    fn load_active(&mut self) -> egui::Image {
-        let uri = Self::to_uri(self.active_file());
+    /// reads the file into the image cache unless it's already there, and returns its uri
+    fn cache_file(&self, path: &Path) -> io::Result<String> {
+        let uri = Self::to_uri(path);
         // skip the io if this uri is already in the cache
-        if self.ctx.try_load_bytes(&uri).is_ok() {
-            return egui::Image::from_uri(uri);
+        if self.ctx.try_load_bytes(&uri).is_err() {
+            let mut buffer = vec![];
+            File::open(path)?.read_to_end(&mut buffer)?;
+            self.ctx.include_bytes(uri.clone(), buffer);
         }
+        Ok(uri)
+    }
 
-        match File::open(self.active_file()) {
+    fn load_active(&mut self) -> egui::Image<'_> {
+        match self.cache_file(self.active_file()) {
+            Ok(uri) => egui::Image::from_uri(uri),
             Err(e) => {
                 error!("{e}");
                 // skip this file so the rest can still be worked with
-                self.files.remove(self.active);
+                self.remove_active();
                 // load the next one instead
                 self.load_active()
             }
-            Ok(mut file) => {
-                let mut buffer = vec![];
-                match file.read_to_end(&mut buffer) {
-                    Err(e) => {
-                        error!("{e}");
-                        // skip this file so the rest can still be worked with
-                        self.files.remove(self.active);
-                        // load the next one instead
-                        self.load_active()
+        }
+    }
+
+    fn apply_rename(&mut self) {
+        let from = self.active_file().clone();
+        let mut id = self.file_id.clone();
+        let state = self.ui_state.clone();
+        let renamed = fs::Ids::read(&self.working_dir, &self.schema.delim)
+            .and_then(|mut ids| self.rename_with(&from, &mut id, &state, &mut ids));
+        match renamed {
+            Ok(to) => {
+                self.file_id = id;
+                self.replace_file(&from, to);
+            }
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    /// tags every selected file with the current selections. Tagged files keep their ids and the rest get new ones.
+    /// Files that can't be renamed are reported without stopping the others.
+    fn apply_batch(&mut self) {
+        self.batch_errors.clear();
+        let state = self.ui_state.clone();
+        let paths: Vec<PathBuf> = self
+            .selected
+            .iter()
+            .map(|&i| self.files[i].clone())
+            .collect();
+        let mut ids = match fs::Ids::read(&self.working_dir, &self.schema.delim) {
+            Ok(ids) => ids,
+            Err(e) => {
+                self.batch_errors.push(e.to_string());
+                return;
+            }
+        };
+        for from in paths {
+            let mut id = match filename::parse::parse_path(&self.schema, &from) {
+                Ok(parsed) => parsed.id,
+                Err(_) => filename::gen_rand_id(&mut self.rng),
+            };
+            match self.rename_with(&from, &mut id, &state, &mut ids) {
+                Ok(to) => self.replace_file(&from, to),
+                Err(e) => {
+                    let name = fs::name_of(&from);
+                    self.batch_errors.push(format!("{name}: {e}"));
+                }
+            }
+        }
+        info!(
+            "Tagged {} of {} selected files",
+            self.selected.len() - self.batch_errors.len(),
+            self.selected.len()
+        );
+    }
+
+    /// renames a file with the given tags. `id` is replaced with a new one if another file already has it.
+    fn rename_with(
+        &mut self,
+        from: &Path,
+        id: &mut String,
+        state: &State,
+        ids: &mut fs::Ids,
+    ) -> Result<PathBuf> {
+        *id = ids.unique_id(from, Some(id.clone()), &mut self.rng)?;
+        let ext = from
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_string());
+        let to = filename::to_path(
+            &self.schema,
+            &self.working_dir,
+            id,
+            state,
+            ext.as_deref(),
+            &self.profile,
+        )?;
+        journal::rename(&self.working_dir, &self.schema, from, &to)?;
+        ids.renamed(from, &to);
+        // a new rename replaces whatever history was undone
+        self.redo.clear();
+        Ok(to)
+    }
+
+    /// reverts the latest rename in the journal, even ones made in previous sessions
+    fn undo(&mut self) {
+        let entry = match journal::read(&self.working_dir) {
+            Ok(entries) => journal::active(&entries).pop(),
+            Err(e) => {
+                error!("{e}");
+                return;
+            }
+        };
+        let Some(entry) = entry else {
+            info!("Nothing to undo");
+            return;
+        };
+        match journal::undo(&self.working_dir, &entry) {
+            Ok(_) => {
+                let from = self.working_dir.join(&entry.to);
+                self.replace_file(&from, self.working_dir.join(&entry.from));
+                self.redo.push(entry);
+            }
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    fn redo(&mut self) {
+        let Some(entry) = self.redo.pop() else {
+            info!("Nothing to redo");
+            return;
+        };
+        let from = self.working_dir.join(&entry.from);
+        let to = self.working_dir.join(&entry.to);
+        match journal::rename(&self.working_dir, &self.schema, &from, &to) {
+            Ok(_) => self.replace_file(&from, to),
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    /// keeps the file lists in sync with a file that was renamed on disk
+    fn replace_file(&mut self, from: &Path, to: PathBuf) {
+        // the image will never be refrenced by its old name again so evict it from the cache
+        self.ctx.forget_image(&Self::to_uri(from));
+
+        // update the list of filenames so the next refresh doesn't fail
+        if let Some(i) = self.files.iter().position(|f| f == from) {
+            self.files[i] = to.clone();
+        }
+        if let Some(i) = self.all_files.iter().position(|f| f == from) {
+            // only this file changed, so the count is adjusted instead of parsing every name again
+            self.tagged =
+                self.tagged + usize::from(self.is_tagged(&to)) - usize::from(self.is_tagged(from));
+            self.all_files[i] = to.clone();
+        }
+
+        // undo and redo can change the tags of the file being looked at
+        if *self.active_file() == to {
+            self.read_active();
+        }
+    }
+
+    fn remove_active(&mut self) {
+        // the selection is by index so it no longer lines up
+        self.selected.clear();
+        let removed = self.files.remove(self.active);
+        self.all_files.retain(|f| *f != removed);
+    }
+
+    fn is_tagged(&self, path: &Path) -> bool {
+        filename::parse::parse_path(&self.schema, path).is_ok()
+    }
+
+    fn count_tagged(&mut self) {
+        self.tagged = self.all_files.iter().filter(|f| self.is_tagged(f)).count();
+    }
+
+    /// handles a click on a file in the filmstrip. A plain click moves to the file and clears the selection.
+    fn click_file(&mut self, i: usize, modifiers: Modifiers) {
+        if select_files(&mut self.selected, self.active, i, modifiers) {
+            self.active = i;
+            self.zoom = 1.0;
+            self.read_active();
+        }
+    }
+
+    /// thumbnails of every file in a row, where several can be selected to tag at once.
+    /// Only the ones scrolled into view are loaded.
+    fn filmstrip(&mut self, ui: &mut egui::Ui) {
+        let step = THUMBNAIL_SIZE + ui.spacing().item_spacing.x;
+        let mut clicked = None;
+        egui::ScrollArea::horizontal().show_viewport(ui, |ui, viewport| {
+            ui.set_width(step * self.files.len() as f32);
+            ui.set_height(THUMBNAIL_SIZE);
+            let first = (viewport.min.x / step).floor().max(0.0) as usize;
+            let last = ((viewport.max.x / step).ceil() as usize).min(self.files.len());
+            for i in first..last {
+                let min = ui.max_rect().min + egui::vec2(i as f32 * step, 0.0);
+                let rect = Rect::from_min_size(min, egui::Vec2::splat(THUMBNAIL_SIZE));
+                let path = &self.files[i];
+                let name = fs::name_of(path);
+                match self.cache_file(path) {
+                    Ok(uri) => {
+                        ui.put(
+                            rect,
+                            egui::Image::from_uri(uri)
+                                .rounding(4.0)
+                                .fit_to_exact_size(rect.size()),
+                        );
                     }
-                    Ok(_) => {
-                        self.ctx.include_bytes(uri.clone(), buffer);
-                        egui::Image::from_uri(uri)
+                    // the file is still listed so it can be picked, just without a preview
+                    Err(_) => {
+                        ui.put(rect, Label::new(&name).truncate(true));
                     }
                 }
+
+                let visuals = ui.visuals();
+                if self.selected.contains(&i) {
+                    let stroke = Stroke::new(3.0, visuals.selection.bg_fill);
+                    ui.painter().rect_stroke(rect.expand(1.0), 4.0, stroke);
+                }
+                if i == self.active {
+                    let stroke = Stroke::new(1.0, visuals.strong_text_color());
+                    ui.painter().rect_stroke(rect.expand(3.0), 4.0, stroke);
+                }
+                let response = ui
+                    .interact(rect, ui.id().with(("thumbnail", i)), Sense::click())
+                    .on_hover_text(name);
+                if response.clicked() {
+                    clicked = Some((i, ui.input(|input| input.modifiers)));
+                }
             }
+        });
+        if let Some((i, modifiers)) = clicked {
+            self.click_file(i, modifiers);
         }
     }
 
-    fn apply_rename(&mut self) {
-        // only apply the rename if there isn't an error generating the new filename
-        if let Ok(filename) = self.mk_filename() {
-            let mut to = self.working_dir.clone();
-            to.push(&filename);
-            match std::fs::rename(self.active_file(), &to) {
-                Ok(()) => info!(
-                    "{} →  {}",
-                    self.active_file()
-                        .file_name()
-                        .map_or("old".into(), |os| os.to_string_lossy()),
-                    filename
-                ),
-                Err(e) => error!("{}", Error::FailedRename(e)),
-            };
+    /// restricts the files being worked on to those matching the query, and to untagged files when skipping tagged ones.
+    /// An empty query matches every file.
+    fn apply_filters(&mut self) {
+        let matches = if self.query.trim().is_empty() {
+            Ok(self.all_files.clone())
+        } else {
+            query::compile(&self.schema, &self.query)
+                .map(|q| query::filter(&self.schema, &q, self.all_files.clone()))
+        };
+
+        match matches {
+            Err(e) => self.filter_error = Some(e.to_string()),
+            Ok(mut files) => {
+                if self.skip_tagged {
+                    files.retain(|f| !self.is_tagged(f));
+                }
+                // the UI always needs a file to display so keep the current set
+                if files.is_empty() {
+                    self.filter_error = Some(if self.skip_tagged {
+                        "Every matching file is already tagged.".to_string()
+                    } else {
+                        "No files match this query.".to_string()
+                    });
+                    return;
+                }
+                self.filter_error = None;
+                self.selected.clear();
+                self.files = files;
+                self.active = 0;
+                self.zoom = 1.0;
+                self.read_active();
+            }
+        }
+    }
+}
 
-            // the image will never be refrenced by its old name again so evict it from the cache
-            self.ctx.forget_image(&Self::to_uri(self.active_file()));
+/// formats numbers with thousands separators: 1040 → 1,040
+fn with_commas(n: usize) -> String {
+    let digits = n.to_string();
+    let mut s = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            s.push(',');
+        }
+        s.push(c);
+    }
+    s
+}
 
-            // update the list of filenames so the next refresh doesn't fail
-            self.files[self.active] = to;
+/// updates the selection for a click on file `i`. Ctrl-click (Cmd-click on macOS) adds or removes one file,
+/// and shift-click selects every file from the active one. Returns whether the clicked file becomes the active one.
+fn select_files(
+    selected: &mut BTreeSet<usize>,
+    active: usize,
+    i: usize,
+    modifiers: Modifiers,
+) -> bool {
+    if modifiers.shift {
+        let range = if i < active { i..=active } else { active..=i };
+        selected.extend(range);
+        false
+    } else if modifiers.command {
+        // the file being looked at counts as selected until something else is
+        if selected.is_empty() {
+            selected.insert(active);
         }
+        if !selected.remove(&i) {
+            selected.insert(i);
+        }
+        false
+    } else {
+        selected.clear();
+        true
     }
 }
 
@@ -224,18 +533,142 @@ pub fn to_empty_state(schema: &Schema) -> State {
         .collect()
 }
 
+/// checkboxes for the keywords under `parent`, with the keywords nested under them in collapsible sections
+fn keyword_tree(
+    ui: &mut egui::Ui,
+    cat: &Category,
+    kws: &mut [(Keyword, bool)],
+    parent: Option<usize>,
+    enabled: &[bool],
+    changed: &mut Option<(usize, bool)>,
+) {
+    for i in (0..kws.len()).filter(|&i| cat.parent(i) == parent) {
+        let mut checkbox = |ui: &mut egui::Ui, kws: &mut [(Keyword, bool)]| {
+            let (kw, tf) = &mut kws[i];
+            if ui
+                .add_enabled(enabled[i], Checkbox::new(tf, kw.name.clone()))
+                .changed()
+            {
+                *changed = Some((i, *tf));
+            }
+        };
+        if (0..kws.len()).any(|j| cat.parent(j) == Some(i)) {
+            let id = ui.make_persistent_id((&cat.name, i));
+            CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                .show_header(ui, |ui| checkbox(ui, kws))
+                .body(|ui| keyword_tree(ui, cat, kws, Some(i), enabled, changed));
+        } else {
+            checkbox(ui, kws);
+        }
+    }
+}
+
+/// the field for a category that takes a value. Dates and numbers start out unset with a button to set them.
+/// Text is only set once what's typed can be written in a name.
+fn value_input(
+    ui: &mut egui::Ui,
+    schema: &Schema,
+    category: usize,
+    kind: &ValueKind,
+    kws: &mut Vec<(Keyword, bool)>,
+    text_inputs: &mut HashMap<usize, String>,
+) {
+    let current = kws.iter().find(|(_, tf)| *tf).map(|(kw, _)| kw.clone());
+    let mut entered = None;
+    ui.horizontal(|ui| {
+        match kind {
+            ValueKind::Text(max) => {
+                let text = text_inputs
+                    .entry(category)
+                    .or_insert_with(|| current.as_ref().map_or(String::new(), |kw| kw.name.clone()));
+                let changed = ui.text_edit_singleline(text).changed();
+                let usable = value::text(schema, text, *max);
+                if changed && text.is_empty() {
+                    entered = Some(None);
+                } else if changed && usable.is_some() {
+                    entered = Some(usable.clone());
+                }
+                if usable.is_none() && !text.is_empty() {
+                    ui.colored_label(Color32::RED, "not usable yet").on_hover_text(
+                        "Without the characters that can't be in a name, this is empty, a keyword id, or the empty marker",
+                    );
+                }
+                return;
+            }
+            _ if current.is_none() => {
+                if ui.button("Set").clicked() {
+                    entered = Some(match kind {
+                        ValueKind::Date(format) => {
+                            Some(value::date(format, chrono::Local::now().date_naive()))
+                        }
+                        ValueKind::Number(min, _) => Some(value::number(*min)),
+                        ValueKind::Text(_) => None,
+                    });
+                }
+                return;
+            }
+            ValueKind::Date(format) => {
+                let id_source = format!("date {category}");
+                let mut date = current
+                    .as_ref()
+                    .and_then(|kw| {
+                        chrono::NaiveDate::parse_from_str(&kw.name, value::DATE_INPUT_FORMAT).ok()
+                    })
+                    .unwrap_or_else(|| chrono::Local::now().date_naive());
+                if ui
+                    .add(DatePickerButton::new(&mut date).id_source(&id_source))
+                    .changed()
+                {
+                    entered = Some(Some(value::date(format, date)));
+                }
+            }
+            ValueKind::Number(min, max) => {
+                let mut n = current
+                    .as_ref()
+                    .and_then(|kw| kw.id.parse().ok())
+                    .unwrap_or(*min);
+                if ui.add(egui::Slider::new(&mut n, *min..=*max)).changed() {
+                    entered = Some(Some(value::number(n)));
+                }
+            }
+        }
+        if ui.button("✖").on_hover_text("Clear the value").clicked() {
+            entered = Some(None);
+        }
+    });
+    if let Some(kw) = entered {
+        *kws = kw.into_iter().map(|kw| (kw, true)).collect();
+    }
+}
+
 impl eframe::App for AppConfig {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if ctx.input(|i| i.key_pressed(Key::ArrowLeft)) {
+        // keys typed into a text field shouldn't also navigate or rename
+        let typing = ctx.memory(|m| m.focused().is_some());
+
+        if !typing && ctx.input(|i| i.key_pressed(Key::ArrowLeft)) {
             self.prev();
         }
 
-        if ctx.input(|i| i.key_pressed(Key::ArrowRight)) {
+        if !typing && ctx.input(|i| i.key_pressed(Key::ArrowRight)) {
             self.next();
         }
 
-        if ctx.input(|i| i.key_pressed(Key::Enter)) {
-            self.apply_rename()
+        if !typing && ctx.input(|i| i.key_pressed(Key::Enter)) {
+            if self.selected.is_empty() {
+                self.apply_rename()
+            } else {
+                self.apply_batch()
+            }
+        }
+
+        // check the more specific shortcut first since shift is ignored when matching
+        if !typing
+            && ctx.input_mut(|i| i.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z))
+        {
+            self.redo()
+        } else if !typing && ctx.input_mut(|i| i.consume_key(Modifiers::COMMAND, Key::Z)) {
+            self.undo()
         }
 
         egui::SidePanel::new(Side::Left, "keyword").show(ctx, |ui| {
@@ -255,17 +688,99 @@ impl eframe::App for AppConfig {
                 ui.separator();
                 ui.add_space(4.0);
 
-                self.ui_state.iter_mut().for_each(|cat| {
-                    ui.label(cat.0.name.clone());
-                    cat.1.iter_mut().for_each(|kw| {
-                        let name = kw.0.name.clone();
-                        ui.checkbox(&mut kw.1, name);
+                let applies: Vec<bool> = (0..self.ui_state.len())
+                    .map(|category| filename::applies(&self.schema, &self.ui_state, category))
+                    .collect();
+                // keywords that an excluding keyword or an unmet condition rule out
+                let selectable: Vec<Vec<bool>> = (0..self.ui_state.len())
+                    .map(|category| {
+                        (0..self.ui_state[category].1.len())
+                            .map(|keyword| {
+                                let at = KeywordRef { category, keyword };
+                                filename::is_selectable(&self.schema, &self.ui_state, at)
+                            })
+                            .collect()
                     })
-                })
+                    .collect();
+                for (i, ((cat, kws), selectable)) in
+                    self.ui_state.iter_mut().zip(selectable).enumerate()
+                {
+                    if let Some(kind) = &cat.value {
+                        ui.label(cat.name.clone())
+                            .on_hover_text(format!("Enter {kind}"));
+                        // the value is cleared when the category stops applying, so what was typed goes with it
+                        if !applies[i] {
+                            self.text_inputs.remove(&i);
+                        }
+                        ui.add_enabled_ui(applies[i], |ui| {
+                            value_input(ui, &self.schema, i, kind, kws, &mut self.text_inputs)
+                        });
+                        continue;
+                    }
+                    ui.label(cat.name.clone())
+                        .on_hover_text(format!("Select {}", cat.requirement));
+                    let max = cat.requirement.max();
+                    let selected = filename::written(cat, kws).len();
+                    // picking another keyword replaces the only one allowed. Otherwise the rest are disabled at the limit,
+                    // except for keywords nested under a selected one since they take its place.
+                    let full = max.is_some_and(|max| max != 1 && selected >= max);
+                    let enabled: Vec<bool> = (0..kws.len())
+                        .map(|k| {
+                            let replaces = (0..kws.len()).any(|j| kws[j].1 && cat.is_under(k, j));
+                            kws[k].1 || ((!full || replaces) && selectable[k])
+                        })
+                        .collect();
+                    let mut changed = None;
+                    keyword_tree(ui, cat, kws, None, &enabled, &mut changed);
+                    match changed {
+                        Some((k, true)) if max == Some(1) => {
+                            kws.iter_mut().for_each(|kw| kw.1 = false);
+                            filename::set_selected(cat, kws, k, true);
+                        }
+                        Some((k, tf)) => filename::set_selected(cat, kws, k, tf),
+                        None => (),
+                    }
+                }
+                // disabled keywords can't be unchecked, so they're cleared when their category stops applying
+                filename::clear_inapplicable(&self.schema, &mut self.ui_state);
             });
         });
 
         egui::TopBottomPanel::new(TopBottomSide::Top, "filename").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(Label::new("query:"));
+                let search = ui
+                    .text_edit_singleline(&mut self.query)
+                    .on_hover_text("e.g. Media:photo AND NOT People:nate");
+                if search.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                    self.apply_filters();
+                }
+
+                let skip_tagged = ui
+                    .checkbox(&mut self.skip_tagged, "Skip tagged")
+                    .on_hover_text("Hide files that already match the schema");
+                if skip_tagged.changed() {
+                    self.apply_filters();
+                }
+
+                ui.add(Label::new(format!(
+                    "{} / {} tagged",
+                    with_commas(self.tagged),
+                    with_commas(self.all_files.len())
+                )));
+                if self.files.len() != self.all_files.len() {
+                    ui.add(Label::new(format!(
+                        "showing {}",
+                        with_commas(self.files.len())
+                    )));
+                }
+
+                if let Some(msg) = &self.filter_error {
+                    ui.visuals_mut().override_text_color = Some(Color32::RED);
+                    ui.add(Label::new(msg));
+                }
+            });
+
             ui.horizontal(|ui| {
                 ui.add(Label::new("filename:"));
 
@@ -298,9 +813,47 @@ impl eframe::App for AppConfig {
                 }
             });
 
+            if !self.selected.is_empty() {
+                ui.horizontal(|ui| {
+                    let apply = ui
+                        .add(Button::new(format!(
+                            "Tag {} selected",
+                            with_commas(self.selected.len())
+                        )))
+                        .on_hover_text("Rename every selected file with these keywords");
+                    if apply.clicked() {
+                        self.apply_batch();
+                    }
+                    if ui.button("Deselect").clicked() {
+                        self.selected.clear();
+                    }
+                });
+            }
+            if !self.batch_errors.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.add(Label::new(format!(
+                        "{} files couldn't be renamed",
+                        self.batch_errors.len()
+                    )));
+                    if ui.button("Dismiss").clicked() {
+                        self.batch_errors.clear();
+                    }
+                });
+                ui.visuals_mut().override_text_color = Some(Color32::RED);
+                for msg in &self.batch_errors {
+                    ui.add(Label::new(msg));
+                }
+            }
+
             match self.mk_filename() {
                 Ok(name) => {
-                    ui.add(Label::new(format!("new name: {name}",)));
+                    let usage = match self.profile.name {
+                        Some(budget) => {
+                            format!("  ({} / {budget})", budget.unit.len(&name))
+                        }
+                        None => String::new(),
+                    };
+                    ui.add(Label::new(format!("new name: {name}{usage}")));
                 }
                 Err(msg) => {
                     ui.horizontal(|ui| {
@@ -311,6 +864,12 @@ impl eframe::App for AppConfig {
             }
         });
 
+        egui::TopBottomPanel::new(TopBottomSide::Bottom, "filmstrip").show(ctx, |ui| {
+            ui.add_space(4.0);
+            self.filmstrip(ui);
+            ui.add_space(4.0);
+        });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             self.zoom *= ctx.input(|i| i.zoom_delta());
 
@@ -326,3 +885,30 @@ impl eframe::App for AppConfig {
         });
     }
 }
+
+#[test]
+fn test_select_files() {
+    let shift = Modifiers::SHIFT;
+    let command = Modifiers::COMMAND;
+    let mut selected = BTreeSet::new();
+
+    assert!(!select_files(&mut selected, 5, 2, shift));
+    assert_eq!(selected, BTreeSet::from([2, 3, 4, 5]));
+    assert!(!select_files(&mut selected, 5, 3, command));
+    assert!(!select_files(&mut selected, 5, 8, command));
+    assert_eq!(selected, BTreeSet::from([2, 4, 5, 8]));
+    assert!(select_files(&mut selected, 5, 1, Modifiers::NONE));
+    assert!(selected.is_empty());
+
+    // the active file is included when starting a selection
+    select_files(&mut selected, 1, 3, command);
+    assert_eq!(selected, BTreeSet::from([1, 3]));
+}
+
+#[test]
+fn test_with_commas() {
+    assert_eq!(with_commas(0), "0");
+    assert_eq!(with_commas(312), "312");
+    assert_eq!(with_commas(1040), "1,040");
+    assert_eq!(with_commas(1234567), "1,234,567");
+}
@@ -0,0 +1,220 @@
+use crate::{
+    filename::{
+        is_id,
+        parse::{parse_path, ParseFilenameError},
+    },
+    schema::{Keyword, Schema},
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    Conforming,
+    /// the name uses ids that keywords used to have, along with the keywords that have them now
+    Outdated(Vec<(String, Keyword)>),
+    /// the name doesn't start with a file id so it was never tagged
+    Untagged,
+    Malformed(Vec<Diagnostic>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Diagnostic {
+    Filename(ParseFilenameError),
+    DuplicateId { id: String, others: Vec<PathBuf> },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Filename(e) => write!(f, "{e}"),
+            Self::DuplicateId { id, others } => {
+                let others: Vec<String> = others
+                    .iter()
+                    .map(|p| {
+                        p.file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .to_string()
+                    })
+                    .collect();
+                write!(f, "File id {id} is also used by {}.", others.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub files: Vec<(PathBuf, Status)>,
+}
+
+impl Report {
+    pub fn conforming(&self) -> usize {
+        self.count(|s| matches!(s, Status::Conforming))
+    }
+
+    pub fn outdated(&self) -> usize {
+        self.count(|s| matches!(s, Status::Outdated(_)))
+    }
+
+    pub fn untagged(&self) -> usize {
+        self.count(|s| matches!(s, Status::Untagged))
+    }
+
+    pub fn malformed(&self) -> usize {
+        self.count(|s| matches!(s, Status::Malformed(_)))
+    }
+
+    fn count(&self, f: impl Fn(&Status) -> bool) -> usize {
+        self.files.iter().filter(|(_, s)| f(s)).count()
+    }
+}
+
+/// classifies every file as conforming to the schema, outdated, untagged, or malformed.
+pub fn check(schema: &Schema, files: Vec<PathBuf>) -> Report {
+    let mut file_ids = Vec::with_capacity(files.len());
+    let mut statuses = Vec::with_capacity(files.len());
+    for path in &files {
+        let (id, status) = match parse_path(schema, path) {
+            Ok(parsed) if parsed.outdated.is_empty() => (Some(parsed.id), Status::Conforming),
+            Ok(parsed) => (Some(parsed.id), Status::Outdated(parsed.outdated)),
+            // a leading delimiter is a tag with a missing id rather than an untagged name
+            Err(ParseFilenameError::InvalidId(id)) if !id.is_empty() => (None, Status::Untagged),
+            Err(e) => (
+                id_prefix(schema, path),
+                Status::Malformed(vec![Diagnostic::Filename(e)]),
+            ),
+        };
+        file_ids.push(id);
+        statuses.push(status);
+    }
+
+    let mut ids: HashMap<&str, Vec<&PathBuf>> = HashMap::new();
+    for (path, id) in files.iter().zip(&file_ids) {
+        if let Some(id) = id {
+            ids.entry(id).or_default().push(path);
+        }
+    }
+
+    // ids are only unique if no other file shares them
+    for ((path, id), status) in files.iter().zip(&file_ids).zip(statuses.iter_mut()) {
+        let Some(id) = id else {
+            continue;
+        };
+        let others: Vec<PathBuf> = ids[id.as_str()]
+            .iter()
+            .filter(|p| **p != path)
+            .map(|p| p.to_path_buf())
+            .collect();
+        if !others.is_empty() {
+            let duplicate = Diagnostic::DuplicateId {
+                id: id.clone(),
+                others,
+            };
+            match status {
+                Status::Malformed(diagnostics) => diagnostics.push(duplicate),
+                _ => *status = Status::Malformed(vec![duplicate]),
+            }
+        }
+    }
+
+    Report {
+        files: files.into_iter().zip(statuses).collect(),
+    }
+}
+
+/// the file id of a name that starts with one even if the rest of it doesn't decode
+fn id_prefix(schema: &Schema, path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let (id, _) = name.split_once(&schema.delim)?;
+    is_id(id).then(|| id.to_string())
+}
+
+#[test]
+fn check_statuses() {
+    let schema = crate::fixtures::media_people();
+    let files: Vec<PathBuf> = [
+        "ABC123-ph-nate.jpg",
+        "IMG_0001.jpg",
+        "ABC124-ph-v-_.jpg",
+        "ABC125-ph--nate.jpg",
+        "-ph-nate.jpg",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect();
+    let report = check(&schema, files);
+
+    assert_eq!(report.files[0].1, Status::Conforming);
+    assert_eq!(report.files[1].1, Status::Untagged);
+    assert!(matches!(
+        &report.files[2].1,
+        Status::Malformed(ds) if matches!(ds[..], [Diagnostic::Filename(ParseFilenameError::Requirement(_))])
+    ));
+    assert_eq!(
+        report.files[3].1,
+        Status::Malformed(vec![Diagnostic::Filename(ParseFilenameError::EmptySegment)])
+    );
+    assert!(matches!(report.files[4].1, Status::Malformed(_)));
+    assert_eq!(
+        (report.conforming(), report.untagged(), report.malformed()),
+        (1, 1, 3)
+    );
+}
+
+#[test]
+fn check_outdated() {
+    let schema = crate::fixtures::with_categories(&[
+        r#"category "Media" (exactly 1) ['photo'/'ph' (was 'p', 'pic'), 'video'/'v']"#,
+    ]);
+    let files: Vec<PathBuf> = [
+        "ABC123-ph.jpg",
+        "ABC124-p.jpg",
+        "ABC125-v.mov",
+        "ABC126-pic.jpg",
+        "ABC126-v.mov",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect();
+    let report = check(&schema, files);
+
+    assert_eq!(report.files[0].1, Status::Conforming);
+    assert!(matches!(
+        &report.files[1].1,
+        Status::Outdated(ids) if ids[0].0 == "p" && ids[0].1.id == "ph"
+    ));
+    // outdated names still have to have unique ids
+    assert!(matches!(&report.files[3].1, Status::Malformed(_)));
+    assert_eq!(
+        (report.conforming(), report.outdated(), report.malformed()),
+        (2, 1, 2)
+    );
+}
+
+#[test]
+fn check_duplicate_ids() {
+    let schema = crate::fixtures::media_people();
+    let files: Vec<PathBuf> = ["ABC123-ph-nate.jpg", "ABC123-v-_.mov", "ABC123-v-bob.mov"]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    let report = check(&schema, files);
+
+    assert_eq!(
+        report.files[0].1,
+        Status::Malformed(vec![Diagnostic::DuplicateId {
+            id: "ABC123".to_string(),
+            others: vec![
+                PathBuf::from("ABC123-v-_.mov"),
+                PathBuf::from("ABC123-v-bob.mov")
+            ]
+        }])
+    );
+    // malformed names still count towards duplicates
+    assert!(matches!(&report.files[2].1, Status::Malformed(ds) if ds.len() == 2));
+}
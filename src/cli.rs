@@ -0,0 +1,608 @@
+use crate::{
+    app::{self, AppConfig},
+    check::{self, Status},
+    error::{Error, Result},
+    filename::{
+        self,
+        limit::{Budget, Profile},
+        value,
+    },
+    fs::{
+        self, journal,
+        plan::{self, Plan},
+    },
+    migrate::{self, Rule},
+    query,
+    schema::{analysis, Schema},
+    State,
+};
+use clap::{Parser, Subcommand};
+use rand::thread_rng;
+use std::{
+    path::{Path, PathBuf},
+    result::Result as StdResult,
+    time::SystemTime,
+};
+
+#[derive(Parser, Debug, Clone)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Opens the GUI when no subcommand is given
+    #[command(flatten)]
+    pub gui: GuiArgs,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct GuiArgs {
+    /// Directory with the files to tag and their schema.q. One named like a subcommand has to be
+    /// given as `gui <dir>` or `./<dir>`.
+    #[arg(default_value = ".")]
+    pub working_dir: PathBuf,
+    /// Skip files that are already tagged
+    #[arg(long)]
+    pub resume: bool,
+    #[command(flatten)]
+    pub target: TargetArgs,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct TargetArgs {
+    /// File system whose name length limits new names must fit in: ntfs, apfs, ext4, dropbox, unlimited, or a custom budget like bytes:200
+    #[arg(long, default_value = "unlimited")]
+    pub target: Profile,
+    /// Limit on the length of the full path like bytes:4096. Overrides the target's.
+    #[arg(long, value_name = "UNIT:MAX")]
+    pub max_path: Option<Budget>,
+}
+
+impl TargetArgs {
+    pub fn profile(&self) -> Profile {
+        Profile {
+            path: self.max_path.or(self.target.path),
+            ..self.target
+        }
+    }
+}
+
+/// for commands that rename many files at once
+#[derive(clap::Args, Debug, Clone)]
+pub struct PlanArgs {
+    /// Print the new names without renaming
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Save the renames to a file to make later with `nametag apply` instead of renaming
+    #[arg(long, value_name = "FILE")]
+    pub plan: Option<PathBuf>,
+    /// How to print the renames
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    pub format: Format,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Tag the files in a directory with the GUI
+    Gui(GuiArgs),
+    /// Report the longest name the schema can produce. Fails if it can exceed the target's limits.
+    CheckSchema {
+        #[arg(default_value = ".")]
+        working_dir: PathBuf,
+        /// Length of the longest extension to allow for, counting the dot
+        #[arg(long, default_value_t = analysis::DEFAULT_EXT_LEN)]
+        ext_len: usize,
+        #[command(flatten)]
+        target: TargetArgs,
+    },
+    /// Report the files whose names don't match the schema. Fails if any are found.
+    Check {
+        #[arg(default_value = ".")]
+        working_dir: PathBuf,
+        /// Only fail on malformed names
+        #[arg(long)]
+        allow_untagged: bool,
+    },
+    /// Print the files whose tags match a query
+    Query {
+        query: String,
+        #[arg(default_value = ".")]
+        working_dir: PathBuf,
+    },
+    /// Set keywords on a single file and rename it
+    Tag {
+        file: PathBuf,
+        /// Select a keyword. Replaces the current keyword in categories that allow at most one.
+        /// Categories that take a value are set to it, with dates given as YYYY-MM-DD.
+        #[arg(long, value_name = "CATEGORY=KEYWORD")]
+        set: Vec<String>,
+        /// Deselect a keyword, or clear a value
+        #[arg(long, value_name = "CATEGORY=KEYWORD")]
+        unset: Vec<String>,
+        /// Start from no keywords instead of the ones already in the filename
+        #[arg(long)]
+        clear: bool,
+        /// Print the new name without renaming
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        target: TargetArgs,
+    },
+    /// Rewrite the names of tagged files in their canonical form
+    Rename {
+        #[arg(default_value = ".")]
+        working_dir: PathBuf,
+        #[command(flatten)]
+        plan: PlanArgs,
+        #[command(flatten)]
+        target: TargetArgs,
+    },
+    /// Rewrite the ids keywords used to have in tagged names to their current ids,
+    /// or carry names made with an older schema over to the current one
+    Migrate {
+        #[arg(default_value = ".")]
+        working_dir: PathBuf,
+        /// Schema the names were made with
+        #[arg(long, value_name = "PATH")]
+        from: Option<PathBuf>,
+        /// Schema to rename the files with. Defaults to the working directory's schema.q.
+        #[arg(long, value_name = "PATH", requires = "from")]
+        to: Option<PathBuf>,
+        /// Move a category to one with a new name, or replace a keyword. A keyword is dropped when nothing follows the =.
+        #[arg(long, value_name = "OLD=NEW", value_parser = migrate::parse_map, requires = "from")]
+        map: Vec<Rule>,
+        /// Select a keyword in a category nothing else was carried over to
+        #[arg(long, value_name = "CATEGORY=KEYWORD", value_parser = migrate::parse_default, requires = "from")]
+        default: Vec<Rule>,
+        #[command(flatten)]
+        plan: PlanArgs,
+        #[command(flatten)]
+        target: TargetArgs,
+    },
+    /// Make the renames saved with --plan, as long as none of the files have changed since
+    Apply { plan: PathBuf },
+    /// Reverse renames recorded in the working directory's journal, latest first
+    Undo {
+        #[arg(default_value = ".")]
+        working_dir: PathBuf,
+        /// Number of renames to undo
+        #[arg(long, default_value_t = 1, conflicts_with = "since")]
+        last: usize,
+        /// Undo every rename since a time like 2024-05-01T12:00:00Z, or a duration ago like 30m
+        #[arg(long, value_parser = parse_time)]
+        since: Option<SystemTime>,
+    },
+}
+
+impl Args {
+    /// `nametag <dir>` is shorthand for `nametag gui <dir>`
+    pub fn into_command(self) -> Command {
+        self.command.unwrap_or(Command::Gui(self.gui))
+    }
+}
+
+pub fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Gui(GuiArgs {
+            working_dir,
+            resume,
+            target,
+        }) => {
+            let working_dir = canonicalize(&working_dir)?;
+            let schema = fs::read_schema_file(&fs::schema_path(&working_dir))?;
+            AppConfig::run_with(schema, working_dir, resume, target.profile())
+        }
+        Command::CheckSchema {
+            working_dir,
+            ext_len,
+            target,
+        } => check_schema(&canonicalize(&working_dir)?, ext_len, &target.profile()),
+        Command::Check {
+            working_dir,
+            allow_untagged,
+        } => check(&canonicalize(&working_dir)?, allow_untagged),
+        Command::Query { query, working_dir } => {
+            let working_dir = canonicalize(&working_dir)?;
+            let schema = fs::read_schema_file(&fs::schema_path(&working_dir))?;
+            let query = query::compile(&schema, &query)?;
+            for path in query::filter(&schema, &query, fs::collect_taggable(&working_dir)?) {
+                println!("{}", path.display());
+            }
+            Ok(())
+        }
+        Command::Tag {
+            file,
+            set,
+            unset,
+            clear,
+            dry_run,
+            target,
+        } => tag(
+            &canonicalize(&file)?,
+            &set,
+            &unset,
+            clear,
+            dry_run,
+            &target.profile(),
+        ),
+        Command::Rename {
+            working_dir,
+            plan,
+            target,
+        } => rename(
+            &canonicalize(&working_dir)?,
+            &plan,
+            &target.profile(),
+            false,
+        ),
+        Command::Migrate {
+            working_dir,
+            from: None,
+            plan,
+            target,
+            ..
+        } => rename(&canonicalize(&working_dir)?, &plan, &target.profile(), true),
+        Command::Migrate {
+            working_dir,
+            from: Some(from),
+            to,
+            map,
+            default,
+            plan,
+            target,
+        } => {
+            let working_dir = canonicalize(&working_dir)?;
+            let to = to.unwrap_or_else(|| fs::schema_path(&working_dir));
+            let rules = [map, default].concat();
+            migrate(&working_dir, &from, &to, &rules, &plan, &target.profile())
+        }
+        Command::Apply { plan } => {
+            let plan = Plan::read(&plan)?;
+            print_plan(&plan, Format::Table);
+            plan.apply()
+        }
+        Command::Undo {
+            working_dir,
+            last,
+            since,
+        } => undo(&canonicalize(&working_dir)?, last, since),
+    }
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf> {
+    std::fs::canonicalize(path).map_err(Error::PathErr)
+}
+
+fn check_schema(working_dir: &Path, ext_len: usize, profile: &Profile) -> Result<()> {
+    let schema = fs::read_schema_file(&fs::schema_path(working_dir))?;
+    let length = analysis::max_name_length(&schema, ext_len);
+    println!("Longest possible name: {length}");
+    let over = analysis::exceeds(profile, working_dir, &length);
+    for (part, budget, len) in &over {
+        println!(
+            "warning: the {part} can be {len} {}, {} over the limit of {budget}",
+            budget.unit,
+            len - budget.max
+        );
+    }
+    if over.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::SchemaTooLong)
+    }
+}
+
+fn check(working_dir: &Path, allow_untagged: bool) -> Result<()> {
+    let schema = fs::read_schema_file(&fs::schema_path(working_dir))?;
+    let report = check::check(&schema, fs::collect_taggable(&working_dir)?);
+    for (path, status) in &report.files {
+        match status {
+            Status::Conforming => (),
+            Status::Outdated(ids) => {
+                for (id, kw) in ids {
+                    println!(
+                        "{}: outdated id \"{id}\", which is now \"{}\" for {}",
+                        fs::name_of(path),
+                        kw.id,
+                        kw.name
+                    );
+                }
+            }
+            Status::Untagged => println!("{}: untagged", fs::name_of(path)),
+            Status::Malformed(diagnostics) => {
+                for diagnostic in diagnostics {
+                    println!("{}: {diagnostic}", fs::name_of(path));
+                }
+            }
+        }
+    }
+    println!(
+        "{} files: {} conforming, {} outdated, {} untagged, {} malformed",
+        report.files.len(),
+        report.conforming(),
+        report.outdated(),
+        report.untagged(),
+        report.malformed()
+    );
+
+    let failed =
+        report.malformed() + report.outdated() + if allow_untagged { 0 } else { report.untagged() };
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(Error::CheckFailed(failed))
+    }
+}
+
+fn tag(
+    file: &Path,
+    set: &[String],
+    unset: &[String],
+    clear: bool,
+    dry_run: bool,
+    profile: &Profile,
+) -> Result<()> {
+    let dir = file.parent().unwrap_or(Path::new("."));
+    let schema = fs::read_schema_file(&fs::schema_path(dir))?;
+
+    // keep the id and keywords of files that are already tagged
+    let (id, mut state) = match filename::parse::parse_path(&schema, file) {
+        Ok(parsed) if clear => (Some(parsed.id), app::to_empty_state(&schema)),
+        Ok(parsed) => (Some(parsed.id), parsed.state),
+        Err(_) => (None, app::to_empty_state(&schema)),
+    };
+    let id = fs::Ids::read(dir, &schema.delim)?.unique_id(file, id, &mut thread_rng())?;
+    for assignment in set {
+        select(&schema, &mut state, assignment, true)?;
+    }
+    for assignment in unset {
+        select(&schema, &mut state, assignment, false)?;
+    }
+
+    let ext = file
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string());
+    let to = filename::to_path(&schema, dir, &id, &state, ext.as_deref(), profile)?;
+    print_rename(file, &to);
+    if !dry_run && to != file {
+        journal::rename(dir, &schema, file, &to)?;
+    }
+    Ok(())
+}
+
+/// rewrites tagged names in their canonical form, or only the ones using former ids when `outdated_only` is set
+fn rename(
+    working_dir: &Path,
+    args: &PlanArgs,
+    profile: &Profile,
+    outdated_only: bool,
+) -> Result<()> {
+    let schema = fs::read_schema_file(&fs::schema_path(working_dir))?;
+    let mut plan = Plan::new(working_dir, &schema);
+    for path in fs::collect_taggable(&working_dir)? {
+        // untagged files are left alone
+        let Ok(parsed) = filename::parse::parse_path(&schema, &path) else {
+            continue;
+        };
+        if outdated_only && parsed.outdated.is_empty() {
+            continue;
+        }
+        match filename::to_path(
+            &schema,
+            working_dir,
+            &parsed.id,
+            &parsed.state,
+            parsed.ext.as_deref(),
+            profile,
+        ) {
+            Ok(to) if to == path => (),
+            Ok(to) => plan.push(&path, Ok(&to))?,
+            Err(e) => plan.push(&path, Err(e.to_string()))?,
+        }
+    }
+    finish(&plan, args)?;
+    Ok(())
+}
+
+/// renames the files made with the `from` schema so they match the `to` schema.
+/// Nothing is renamed unless every file can be.
+fn migrate(
+    working_dir: &Path,
+    from: &Path,
+    to: &Path,
+    rules: &[Rule],
+    args: &PlanArgs,
+    profile: &Profile,
+) -> Result<()> {
+    let old = fs::read_schema_file(from)?;
+    let new = fs::read_schema_file(to)?;
+    migrate::check_rules(&old, &new, rules)?;
+
+    let mut plan = Plan::new(working_dir, &new);
+    for path in fs::collect_taggable(&working_dir)? {
+        // files already migrated, and untagged files, are left alone
+        if filename::parse::parse_path(&new, &path).is_ok() {
+            continue;
+        }
+        let Ok(parsed) = filename::parse::parse_path(&old, &path) else {
+            continue;
+        };
+        let renamed = migrate::migrate(&parsed.state, &new, rules)
+            .map_err(Error::from)
+            .and_then(|state| {
+                filename::to_path(
+                    &new,
+                    working_dir,
+                    &parsed.id,
+                    &state,
+                    parsed.ext.as_deref(),
+                    profile,
+                )
+                .map_err(Error::from)
+            });
+        match renamed {
+            Ok(renamed) if renamed == path => (),
+            Ok(renamed) => plan.push(&path, Ok(&renamed))?,
+            Err(e) => plan.push(&path, Err(e.to_string()))?,
+        }
+    }
+    if plan.failed() > 0 {
+        print_plan(&plan, args.format);
+        return Err(Error::MigrateFailed(plan.failed()));
+    }
+
+    if finish(&plan, args)? && *to != fs::schema_path(working_dir) {
+        println!(
+            "The files now match {}. Copy it to schema.q to keep working with them.",
+            to.display()
+        );
+    }
+    Ok(())
+}
+
+/// prints the plan, then saves it or makes the renames unless it's a dry run. Returns whether the renames were made.
+fn finish(plan: &Plan, args: &PlanArgs) -> Result<bool> {
+    print_plan(plan, args.format);
+    if let Some(path) = &args.plan {
+        plan.write(path)?;
+        Ok(false)
+    } else if args.dry_run {
+        Ok(false)
+    } else {
+        plan.apply()?;
+        Ok(true)
+    }
+}
+
+fn undo(working_dir: &Path, last: usize, since: Option<SystemTime>) -> Result<()> {
+    let active = journal::active(&journal::read(working_dir)?);
+    let n = match since {
+        Some(since) => active.iter().rev().take_while(|e| e.time >= since).count(),
+        None => last,
+    };
+    for entry in active.iter().rev().take(n) {
+        journal::undo(working_dir, entry)?;
+        println!("{} → {}", entry.to, entry.from);
+    }
+    Ok(())
+}
+
+/// accepts a timestamp, or a duration which is counted back from now
+fn parse_time(s: &str) -> StdResult<SystemTime, String> {
+    humantime::parse_rfc3339_weak(s)
+        .or_else(|_| humantime::parse_duration(s).map(|d| SystemTime::now() - d))
+        .map_err(|_| "expected a time like 2024-05-01T12:00:00Z or a duration like 30m".to_string())
+}
+
+/// applies a `CATEGORY=KEYWORD` argument to the state. Keywords can be given by name or id.
+/// Deselecting a value clears it whatever value is given.
+fn select(schema: &Schema, state: &mut State, assignment: &str, value: bool) -> Result<()> {
+    let (category, keyword) = assignment
+        .split_once('=')
+        .ok_or(Error::ExpectedAssignment(assignment.to_string()))?;
+    let (cat, kws) = state
+        .iter_mut()
+        .find(|(cat, _)| cat.name == category)
+        .ok_or(Error::UnknownCategory(category.to_string()))?;
+    if let Some(kind) = &cat.value {
+        *kws = if value {
+            let kw = value::from_input(schema, kind, keyword).ok_or(Error::InvalidValue {
+                category: category.to_string(),
+                value: keyword.to_string(),
+            })?;
+            vec![(kw, true)]
+        } else {
+            vec![]
+        };
+        return Ok(());
+    }
+    let i = kws
+        .iter()
+        .position(|(kw, _)| kw.matches(keyword))
+        .ok_or(Error::UnknownKeyword {
+            category: category.to_string(),
+            keyword: keyword.to_string(),
+        })?;
+
+    if value && cat.requirement.max() == Some(1) {
+        kws.iter_mut().for_each(|(_, tf)| *tf = false);
+    }
+    filename::set_selected(cat, kws, i, value);
+    Ok(())
+}
+
+fn print_rename(from: &Path, to: &Path) {
+    println!("{} → {}", fs::name_of(from), fs::name_of(to));
+}
+
+fn print_plan(plan: &Plan, format: Format) {
+    if format == Format::Json {
+        println!("{}", plan.to_json());
+        return;
+    }
+    let width = plan
+        .renames
+        .iter()
+        .map(|rename| rename.from.chars().count())
+        .max()
+        .unwrap_or(0);
+    for rename in &plan.renames {
+        match &rename.status {
+            plan::Status::Ready { to } => println!("ready   {:width$} → {to}", rename.from),
+            plan::Status::Failed { error } => println!("failed  {:width$}   {error}", rename.from),
+        }
+    }
+}
+
+#[test]
+fn select_assignments() {
+    use crate::fixtures::{self, MEDIA};
+    let schema = fixtures::media_people();
+    let mut state = app::to_empty_state(&schema);
+
+    select(&schema, &mut state, "Media=art", true).unwrap();
+    // keywords can be given by id, and single keyword categories are replaced
+    select(&schema, &mut state, "Media=ph", true).unwrap();
+    select(&schema, &mut state, "People=nate", true).unwrap();
+    select(&schema, &mut state, "People=alice", true).unwrap();
+    select(&schema, &mut state, "People=nate", false).unwrap();
+    assert_eq!(filename::generate(&schema, &state), Ok("ph-al".to_string()));
+
+    assert!(matches!(
+        select(&schema, &mut state, "Media", true),
+        Err(Error::ExpectedAssignment(_))
+    ));
+    assert!(matches!(
+        select(&schema, &mut state, "Place=home", true),
+        Err(Error::UnknownCategory(_))
+    ));
+    assert!(matches!(
+        select(&schema, &mut state, "Media=nate", true),
+        Err(Error::UnknownKeyword { .. })
+    ));
+
+    let schema =
+        fixtures::with_categories(&[MEDIA, r#"date "Taken" "%Y%m%d""#, r#"number "Rating" 1 5"#]);
+    let mut state = app::to_empty_state(&schema);
+    select(&schema, &mut state, "Media=art", true).unwrap();
+    select(&schema, &mut state, "Taken=2024-03-01", true).unwrap();
+    select(&schema, &mut state, "Rating=4", true).unwrap();
+    assert_eq!(
+        filename::generate(&schema, &state),
+        Ok("art-20240301-4".to_string())
+    );
+    select(&schema, &mut state, "Rating=", false).unwrap();
+    assert_eq!(
+        filename::generate(&schema, &state),
+        Ok("art-20240301-_".to_string())
+    );
+    assert!(matches!(
+        select(&schema, &mut state, "Rating=9", true),
+        Err(Error::InvalidValue { .. })
+    ));
+}
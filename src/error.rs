@@ -1,5 +1,10 @@
-use crate::schema::{SchemaParseError, SchemaTypeCheckError};
-use std::{error::Error as StdError, fmt, io, result::Result as StdResult};
+use crate::{
+    filename::{GenerateFilenameError, ID_ATTEMPTS},
+    migrate::MigrateError,
+    query::QueryError,
+    schema::Diagnostic,
+};
+use std::{error::Error as StdError, fmt, io, path::PathBuf, result::Result as StdResult};
 use tracing::subscriber::SetGlobalDefaultError;
 use Error::*;
 
@@ -7,24 +12,60 @@ pub type Result<T> = StdResult<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
-    Parse(SchemaParseError),
-    Typecheck(SchemaTypeCheckError),
+    Schema(Vec<Diagnostic>),
+    Query(QueryError),
+    Generate(Box<GenerateFilenameError>),
+    Migrate(MigrateError),
     Eframe(eframe::Error),
     CantOpenWorkingDir(io::Error),
     WorkingDirScan(io::Error),
     EmptyWorkingDir,
     FailedRename(io::Error),
+    RenameCollision(PathBuf),
+    /// every id that was tried is already in use
+    NoFreeId,
     FailedToOpen(io::Error),
     FailedToReadContents(io::Error),
     LoggerFailed(SetGlobalDefaultError),
     PathErr(io::Error),
+    ExpectedAssignment(String),
+    UnknownCategory(String),
+    UnknownKeyword {
+        category: String,
+        keyword: String,
+    },
+    InvalidValue {
+        category: String,
+        value: String,
+    },
+    CheckFailed(usize),
+    MigrateFailed(usize),
+    SchemaTooLong,
+    JournalRead(io::Error),
+    JournalWrite(io::Error),
+    MalformedJournal(usize),
+    PlanRead(io::Error),
+    PlanWrite(io::Error),
+    MalformedPlan(serde_json::Error),
+    StalePlan(usize),
+    PlanTargetsCollide(String),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Parse(e) => write!(f, "{e}"),
-            Typecheck(e) => write!(f, "{e}"),
+            Schema(diagnostics) => {
+                for diagnostic in diagnostics {
+                    writeln!(f, "{diagnostic}\n")?;
+                }
+                match diagnostics.len() {
+                    1 => write!(f, "Found 1 error in the schema"),
+                    n => write!(f, "Found {n} errors in the schema"),
+                }
+            }
+            Query(e) => write!(f, "{e}"),
+            Generate(e) => write!(f, "{e}"),
+            Migrate(e) => write!(f, "{e}"),
             Eframe(e) => write!(f, "{e}"),
             CantOpenWorkingDir(e) => write!(f, "Cannot open working directory: {e}"),
             WorkingDirScan(e) => write!(
@@ -33,10 +74,53 @@ impl fmt::Display for Error {
             ),
             EmptyWorkingDir => write!(f, "Working directory has nothing to work with"),
             FailedRename(e) => write!(f, "Failed rename: {e}"),
+            NoFreeId => write!(
+                f,
+                "Couldn't find a file id that isn't already in use after {} tries",
+                ID_ATTEMPTS
+            ),
+            RenameCollision(path) => write!(
+                f,
+                "Failed rename: {} already exists and will not be overwritten",
+                path.display()
+            ),
             FailedToOpen(e) => write!(f, "Failed to open file: {e}"),
             LoggerFailed(e) => write!(f, "Failed to set up logger: {e}"),
             FailedToReadContents(e) => write!(f, "Failed read file contents: {e}"),
             PathErr(e) => write!(f, "Issue with path: {e}"),
+            ExpectedAssignment(s) => {
+                write!(f, "Expected CATEGORY=KEYWORD but got \"{s}\"")
+            }
+            UnknownCategory(name) => write!(f, "Unknown category \"{name}\""),
+            UnknownKeyword { category, keyword } => {
+                write!(f, "Category {category} has no keyword \"{keyword}\"")
+            }
+            InvalidValue { category, value } => {
+                write!(
+                    f,
+                    "\"{value}\" is not a valid value for category {category}"
+                )
+            }
+            CheckFailed(n) => write!(f, "{n} files do not match the schema"),
+            MigrateFailed(n) => write!(f, "{n} files can't be migrated, so nothing was renamed"),
+            SchemaTooLong => write!(
+                f,
+                "Schema can produce names that are too long for the target"
+            ),
+            JournalRead(e) => write!(f, "Failed to read the rename journal: {e}"),
+            JournalWrite(e) => write!(f, "Failed to record the rename in the journal: {e}"),
+            MalformedJournal(line) => write!(f, "Rename journal is malformed at line {line}"),
+            PlanRead(e) => write!(f, "Failed to read the plan: {e}"),
+            PlanWrite(e) => write!(f, "Failed to save the plan: {e}"),
+            MalformedPlan(e) => write!(f, "Plan is malformed: {e}"),
+            StalePlan(n) => write!(
+                f,
+                "{n} files changed since the plan was made, so nothing was renamed. Make the plan again."
+            ),
+            PlanTargetsCollide(to) => write!(
+                f,
+                "More than one file in the plan would be renamed to {to}, so nothing was renamed"
+            ),
         }
     }
 }
@@ -44,9 +128,23 @@ impl fmt::Display for Error {
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
-            EmptyWorkingDir => None,
-            Parse(e) => Some(e),
-            Typecheck(e) => Some(e),
+            EmptyWorkingDir
+            | Schema(_)
+            | ExpectedAssignment(_)
+            | UnknownCategory(_)
+            | UnknownKeyword { .. }
+            | InvalidValue { .. }
+            | CheckFailed(_)
+            | MigrateFailed(_)
+            | SchemaTooLong
+            | MalformedJournal(_)
+            | StalePlan(_)
+            | PlanTargetsCollide(_)
+            | NoFreeId
+            | RenameCollision(_) => None,
+            Query(e) => Some(e),
+            Generate(e) => Some(e.as_ref()),
+            Migrate(e) => Some(e),
             Eframe(e) => Some(e),
             CantOpenWorkingDir(e) => Some(e),
             WorkingDirScan(e) => Some(e),
@@ -55,19 +153,30 @@ impl StdError for Error {
             LoggerFailed(e) => Some(e),
             FailedToReadContents(e) => Some(e),
             PathErr(e) => Some(e),
+            JournalRead(e) => Some(e),
+            JournalWrite(e) => Some(e),
+            PlanRead(e) => Some(e),
+            PlanWrite(e) => Some(e),
+            MalformedPlan(e) => Some(e),
         }
     }
 }
 
-impl From<SchemaParseError> for Error {
-    fn from(e: SchemaParseError) -> Self {
-        Parse(e)
+impl From<QueryError> for Error {
+    fn from(e: QueryError) -> Self {
+        Query(e)
+    }
+}
+
+impl From<GenerateFilenameError> for Error {
+    fn from(e: GenerateFilenameError) -> Self {
+        Generate(Box::new(e))
     }
 }
 
-impl From<SchemaTypeCheckError> for Error {
-    fn from(e: SchemaTypeCheckError) -> Self {
-        Typecheck(e)
+impl From<MigrateError> for Error {
+    fn from(e: MigrateError) -> Self {
+        Migrate(e)
     }
 }
 
@@ -0,0 +1,204 @@
+use super::GenerateFilenameError;
+use std::{fmt, path::Path, str::FromStr};
+
+/// how a file system measures the length of a name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Chars,
+    /// UTF-8 bytes
+    Bytes,
+    /// UTF-16 code units
+    Utf16,
+}
+
+impl Unit {
+    pub fn len(self, s: &str) -> usize {
+        match self {
+            Self::Chars => s.chars().count(),
+            Self::Bytes => s.len(),
+            Self::Utf16 => s.encode_utf16().count(),
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chars => write!(f, "characters"),
+            Self::Bytes => write!(f, "bytes"),
+            Self::Utf16 => write!(f, "UTF-16 units"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Budget {
+    pub unit: Unit,
+    pub max: usize,
+}
+
+impl Budget {
+    pub const fn new(unit: Unit, max: usize) -> Self {
+        Budget { unit, max }
+    }
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.max, self.unit)
+    }
+}
+
+/// parses budgets written as `<unit>:<max>` such as `bytes:255`
+impl FromStr for Budget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (unit, max) = s
+            .split_once(':')
+            .ok_or(format!("expected <unit>:<max> but got \"{s}\""))?;
+        let unit = match unit {
+            "chars" => Unit::Chars,
+            "bytes" => Unit::Bytes,
+            "utf16" => Unit::Utf16,
+            _ => {
+                return Err(format!(
+                    "unknown unit \"{unit}\". Expected chars, bytes, or utf16"
+                ))
+            }
+        };
+        let max = max
+            .parse()
+            .map_err(|_| format!("expected a number but got \"{max}\""))?;
+        Ok(Budget { unit, max })
+    }
+}
+
+/// the length limits of the file system files are being named for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Profile {
+    pub name: Option<Budget>,
+    pub path: Option<Budget>,
+}
+
+impl Profile {
+    pub const NTFS: Profile = Profile {
+        name: Some(Budget::new(Unit::Utf16, 255)),
+        // MAX_PATH for windows apps that haven't opted in to long paths
+        path: Some(Budget::new(Unit::Utf16, 260)),
+    };
+    pub const APFS: Profile = Profile {
+        name: Some(Budget::new(Unit::Chars, 255)),
+        path: Some(Budget::new(Unit::Bytes, 1024)),
+    };
+    pub const EXT4: Profile = Profile {
+        name: Some(Budget::new(Unit::Bytes, 255)),
+        path: Some(Budget::new(Unit::Bytes, 4096)),
+    };
+    pub const DROPBOX: Profile = Profile {
+        name: Some(Budget::new(Unit::Chars, 255)),
+        path: None,
+    };
+
+    /// checks the name, and the full path it would have in the directory, against the limits.
+    pub fn check(&self, dir: &Path, name: &str) -> Result<(), GenerateFilenameError> {
+        if let Some(budget) = self.name {
+            check_budget(Part::Name, budget, name)?;
+        }
+        if let Some(budget) = self.path {
+            check_budget(Part::Path, budget, &dir.join(name).to_string_lossy())?;
+        }
+        Ok(())
+    }
+}
+
+/// parses the name of a known file system, or a custom name budget like `bytes:200`
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ntfs" => Ok(Self::NTFS),
+            "apfs" => Ok(Self::APFS),
+            "ext4" => Ok(Self::EXT4),
+            "dropbox" => Ok(Self::DROPBOX),
+            "unlimited" => Ok(Self::default()),
+            _ if s.contains(':') => s.parse().map(|budget| Profile {
+                name: Some(budget),
+                path: None,
+            }),
+            _ => Err(format!(
+                "unknown target \"{s}\". Expected ntfs, apfs, ext4, dropbox, unlimited, or <unit>:<max>"
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Part {
+    Name,
+    Path,
+}
+
+impl fmt::Display for Part {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name => write!(f, "name"),
+            Self::Path => write!(f, "full path"),
+        }
+    }
+}
+
+fn check_budget(part: Part, budget: Budget, s: &str) -> Result<(), GenerateFilenameError> {
+    let len = budget.unit.len(s);
+    if len > budget.max {
+        Err(GenerateFilenameError::TooLong { part, budget, len })
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn profile_limits() {
+    let dir = Path::new("/photos");
+    // 131 characters but 393 UTF-8 bytes
+    let name = "子".repeat(131);
+    assert!(Profile::APFS.check(dir, &name).is_ok());
+    assert!(Profile::NTFS.check(dir, &name).is_ok());
+    assert_eq!(
+        Profile::EXT4.check(dir, &name),
+        Err(GenerateFilenameError::TooLong {
+            part: Part::Name,
+            budget: Budget::new(Unit::Bytes, 255),
+            len: 393,
+        })
+    );
+
+    let custom: Profile = "chars:10".parse().unwrap();
+    assert!(custom.check(dir, "ABC123-ph").is_ok());
+    assert!(custom.check(dir, "ABC123-ph-nate").is_err());
+
+    let deep = Path::new("/").join("a".repeat(300));
+    assert!(matches!(
+        Profile::NTFS.check(&deep, "ABC123-ph.jpg"),
+        Err(GenerateFilenameError::TooLong {
+            part: Part::Path,
+            ..
+        })
+    ));
+    assert!(Profile::default().check(&deep, &name).is_ok());
+}
+
+#[test]
+fn parse_profile() {
+    assert_eq!("NTFS".parse(), Ok(Profile::NTFS));
+    assert_eq!(
+        "utf16:127".parse(),
+        Ok(Profile {
+            name: Some(Budget::new(Unit::Utf16, 127)),
+            path: None
+        })
+    );
+    assert!("fat32".parse::<Profile>().is_err());
+    assert!("bytes:lots".parse::<Profile>().is_err());
+}
@@ -1,33 +1,60 @@
+pub mod limit;
 pub mod parse;
+pub mod value;
 
-use crate::schema::{
-    Category,
-    Requirement::{self, *},
-    Schema,
-};
+use crate::schema::{Category, Constraint, Keyword, KeywordRef, Requirement, Schema};
 use crate::State;
 use core::fmt;
+use limit::{Budget, Part, Profile};
 use rand::Rng;
 use rand::{
     distributions::{Distribution, Uniform},
     rngs::ThreadRng,
 };
-use std::error::Error as StdError;
+use std::{
+    error::Error as StdError,
+    path::{Path, PathBuf},
+};
 use GenerateFilenameError::*;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum GenerateFilenameError {
     RequirementMismatch {
-        category: Category,
+        category: Box<Category>,
         expected: Requirement,
         got: usize,
     },
+    TooLong {
+        part: Part,
+        budget: Budget,
+        len: usize,
+    },
+    Requires {
+        keyword: Keyword,
+        required: Keyword,
+    },
+    Excludes(Keyword, Keyword),
+    /// keywords are selected in a category that only applies when the condition is
+    Inapplicable {
+        category: Box<Category>,
+        condition: Keyword,
+    },
+    /// a value that isn't one its category takes, or can't be told apart from the rest of the name
+    InvalidValue {
+        category: Box<Category>,
+        value: String,
+    },
 }
 
 impl fmt::Display for GenerateFilenameError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::RequirementMismatch { category, expected, got } => write!(f, "Category {} has a tag requirement of {expected}, but there were {got} keywords found.", category.name)
+            Self::RequirementMismatch { category, expected, got } => write!(f, "Category {} has a tag requirement of {expected}, but there were {got} keywords found.", category.name),
+            Self::TooLong { part, budget, len } => write!(f, "The new {part} is {len} {}, which is {} over the limit of {}.", budget.unit, len - budget.max, budget.max),
+            Self::Requires { keyword, required } => write!(f, "Keyword {} can only be selected along with {}.", keyword.name, required.name),
+            Self::Excludes(a, b) => write!(f, "Keywords {} and {} can't both be selected.", a.name, b.name),
+            Self::Inapplicable { category, condition } => write!(f, "Category {} only applies when {} is selected.", category.name, condition.name),
+            Self::InvalidValue { category, value } => write!(f, "\"{value}\" is not a valid value for category {}.", category.name),
         }
     }
 }
@@ -36,59 +63,222 @@ impl StdError for GenerateFilenameError {}
 
 pub fn generate(schema: &Schema, state: &State) -> Result<String, GenerateFilenameError> {
     let mut name = String::new();
-    for (cat, kws) in state {
-        let ids: Vec<String> = kws
-            .iter()
-            .filter_map(|(kw, tf)| if *tf { Some(kw.id.clone()) } else { None })
+    for (i, (cat, kws)) in state.iter().enumerate() {
+        let ids: Vec<String> = written(cat, kws)
+            .into_iter()
+            .map(|i| kws[i].0.id.clone())
             .collect();
-        match cat.requirement {
-            expected @ Exactly(n) if ids.len() != (n as usize) => Err(RequirementMismatch {
-                category: cat.clone(),
-                expected,
-                got: ids.len(),
-            }),
-            expected @ AtMost(n) if ids.len() > (n as usize) => Err(RequirementMismatch {
-                category: cat.clone(),
-                expected,
-                got: ids.len(),
-            }),
-            expected @ AtLeast(n) if ids.len() < (n as usize) => Err(RequirementMismatch {
-                category: cat.clone(),
-                expected,
-                got: ids.len(),
-            }),
-            _ => {
-                if ids.is_empty() {
-                    name.push_str(&schema.empty);
-                    name.push_str(&schema.delim)
-                }
-                for id in ids {
-                    name.push_str(&id);
-                    name.push_str(&schema.delim)
+        // categories that only apply sometimes are checked along with the other constraints
+        if schema.conditions(i).is_empty() {
+            check_requirement(cat, ids.len())?;
+        }
+        if let Some(kind) = &cat.value {
+            for id in &ids {
+                if value::decode(schema, kind, id).is_none() {
+                    return Err(InvalidValue {
+                        category: Box::new(cat.clone()),
+                        value: id.clone(),
+                    });
                 }
-                Ok(())
             }
-        }?;
+        }
+        if ids.is_empty() {
+            name.push_str(&schema.empty);
+            name.push_str(&schema.delim)
+        }
+        for id in ids {
+            name.push_str(&id);
+            name.push_str(&schema.delim)
+        }
     }
 
+    check_constraints(schema, state)?;
+
     // remove the last delimeter added
-    name.pop();
+    if let Some(stripped) = name.strip_suffix(&schema.delim) {
+        name.truncate(stripped.len());
+    }
     Ok(name)
 }
 
-pub fn gen_rand_id(rng: &mut ThreadRng) -> String {
-    (0..6)
+/// generates the full filename: the file id, the keywords, and the extension.
+pub fn to_filename(
+    schema: &Schema,
+    id: &str,
+    state: &State,
+    ext: Option<&str>,
+) -> Result<String, GenerateFilenameError> {
+    let name = generate(schema, state)?;
+    let delim = &schema.delim;
+    let ext = ext.map_or(String::new(), |ext| format!(".{ext}"));
+    Ok(format!("{id}{delim}{name}{ext}"))
+}
+
+/// generates the full path the file will have in the directory, checking it fits within the profile's limits.
+pub fn to_path(
+    schema: &Schema,
+    dir: &Path,
+    id: &str,
+    state: &State,
+    ext: Option<&str>,
+    profile: &Profile,
+) -> Result<PathBuf, GenerateFilenameError> {
+    let name = to_filename(schema, id, state, ext)?;
+    profile.check(dir, &name)?;
+    Ok(dir.join(name))
+}
+
+/// the selected keywords that go in the name. A keyword with a selected keyword nested under it is implied by that one.
+pub fn written(category: &Category, kws: &[(Keyword, bool)]) -> Vec<usize> {
+    (0..kws.len())
+        .filter(|&i| kws[i].1)
+        .filter(|&i| !(0..kws.len()).any(|j| kws[j].1 && category.parent(j) == Some(i)))
+        .collect()
+}
+
+/// selects a keyword along with the ones it's nested under, or deselects it along with the ones nested under it
+pub fn set_selected(
+    category: &Category,
+    kws: &mut [(Keyword, bool)],
+    keyword: usize,
+    selected: bool,
+) {
+    kws[keyword].1 = selected;
+    for (i, kw) in kws.iter_mut().enumerate() {
+        let related = if selected {
+            category.is_under(keyword, i)
+        } else {
+            category.is_under(i, keyword)
+        };
+        if related {
+            kw.1 = selected;
+        }
+    }
+}
+
+/// checks that the number of keywords selected for a category satisfies its requirement.
+pub fn check_requirement(category: &Category, got: usize) -> Result<(), GenerateFilenameError> {
+    if category.requirement.allows(got) {
+        Ok(())
+    } else {
+        Err(RequirementMismatch {
+            category: Box::new(category.clone()),
+            expected: category.requirement,
+            got,
+        })
+    }
+}
+
+/// checks the rules between keywords, and the requirements of categories that only apply when a keyword is selected
+pub fn check_constraints(schema: &Schema, state: &State) -> Result<(), GenerateFilenameError> {
+    for (i, (cat, kws)) in state.iter().enumerate() {
+        let conditions = schema.conditions(i);
+        if conditions.is_empty() {
+            continue;
+        }
+        let count = written(cat, kws).len();
+        match conditions.into_iter().find(|at| !is_selected(state, *at)) {
+            Some(at) if count > 0 => {
+                return Err(Inapplicable {
+                    category: Box::new(cat.clone()),
+                    condition: schema.keyword(at).clone(),
+                })
+            }
+            Some(_) => (),
+            None => check_requirement(cat, count)?,
+        }
+    }
+    for constraint in &schema.constraints {
+        match *constraint {
+            Constraint::Requires(a, b) if is_selected(state, a) && !is_selected(state, b) => {
+                return Err(Requires {
+                    keyword: schema.keyword(a).clone(),
+                    required: schema.keyword(b).clone(),
+                })
+            }
+            Constraint::Excludes(a, b) if is_selected(state, a) && is_selected(state, b) => {
+                return Err(Excludes(
+                    schema.keyword(a).clone(),
+                    schema.keyword(b).clone(),
+                ))
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// whether every keyword the category depends on is selected
+pub fn applies(schema: &Schema, state: &State, category: usize) -> bool {
+    schema
+        .conditions(category)
+        .into_iter()
+        .all(|c| is_selected(state, c))
+}
+
+/// whether the keyword can be selected given what else is: its category has to apply and nothing selected can exclude it
+pub fn is_selectable(schema: &Schema, state: &State, at: KeywordRef) -> bool {
+    applies(schema, state, at.category)
+        && schema.constraints.iter().all(|c| match *c {
+            Constraint::Excludes(a, b) if a == at => !is_selected(state, b),
+            Constraint::Excludes(a, b) if b == at => !is_selected(state, a),
+            _ => true,
+        })
+}
+
+/// deselects the keywords in categories that no longer apply
+pub fn clear_inapplicable(schema: &Schema, state: &mut State) {
+    // clearing one category can stop another that depends on it from applying
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in 0..state.len() {
+            let applies = applies(schema, state, i);
+            if state[i].0.value.is_some() && !applies && !state[i].1.is_empty() {
+                state[i].1.clear();
+                changed = true;
+            }
+            for kw in state[i].1.iter_mut().filter(|(_, tf)| *tf && !applies) {
+                kw.1 = false;
+                changed = true;
+            }
+        }
+    }
+}
+
+fn is_selected(state: &State, at: KeywordRef) -> bool {
+    state[at.category].1[at.keyword].1
+}
+
+pub fn gen_rand_id<R: Rng + ?Sized>(rng: &mut R) -> String {
+    (0..ID_LEN)
         .map(|_| rng.sample(IDChars) as char)
         .collect::<String>()
 }
 
+/// how many random ids are tried before giving up on finding one that isn't in use
+pub const ID_ATTEMPTS: usize = 1000;
+
+/// generates ids until one isn't already in use, or `None` if none of `ID_ATTEMPTS` of them are free
+pub fn gen_unique_id(rng: &mut ThreadRng, in_use: impl Fn(&str) -> bool) -> Option<String> {
+    (0..ID_ATTEMPTS)
+        .map(|_| gen_rand_id(rng))
+        .find(|id| !in_use(id))
+}
+
+/// returns true if the string could have been generated by `gen_rand_id`
+pub fn is_id(s: &str) -> bool {
+    s.len() == ID_LEN && s.bytes().all(|b| ID_CHARSET.contains(&b))
+}
+
+pub const ID_LEN: usize = 6;
+const ID_CHARSET: &[u8] = b"ABCDEFGHIJKLMNPQRSTUVWXYZ123456789";
+
 struct IDChars;
 
 impl Distribution<u8> for IDChars {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u8 {
-        const RANGE: usize = 25 + 9;
-        const CHARSET: &[u8] = b"ABCDEFGHIJKLMNPQRSTUVWXYZ123456789";
-        let range = Uniform::new(0, RANGE);
-        CHARSET[range.sample(rng)]
+        let range = Uniform::new(0, ID_CHARSET.len());
+        ID_CHARSET[range.sample(rng)]
     }
 }
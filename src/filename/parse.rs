@@ -0,0 +1,673 @@
+use super::{
+    check_constraints, check_requirement, is_id, set_selected, value, GenerateFilenameError,
+};
+use crate::schema::{Category, Keyword, Schema};
+use crate::State;
+use std::{error::Error as StdError, fmt, path::Path};
+use ParseFilenameError::*;
+
+/// the pieces of a filename that was generated with a schema
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsedFilename {
+    pub id: String,
+    pub state: State,
+    pub ext: Option<String>,
+    /// former ids the name was written with, along with the keywords that have them now
+    pub outdated: Vec<(String, Keyword)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseFilenameError {
+    NotUnicode,
+    InvalidId(String),
+    EmptySegment,
+    UnknownKeyword(String),
+    DuplicateKeyword {
+        category: Box<Category>,
+        keyword: Keyword,
+    },
+    /// a keyword written along with one nested under it
+    ImpliedKeyword {
+        category: Box<Category>,
+        keyword: Keyword,
+        by: Keyword,
+    },
+    MissingEmpty(Box<Category>),
+    MissingCategory(Box<Category>),
+    UnexpectedSegment(String),
+    InvalidValue {
+        category: Box<Category>,
+        value: String,
+    },
+    Requirement(GenerateFilenameError),
+}
+
+impl fmt::Display for ParseFilenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotUnicode => write!(f, "Filename is not valid unicode."),
+            InvalidId(id) => write!(f, "\"{id}\" is not a valid file id."),
+            EmptySegment => write!(f, "Filename contains an empty segment between delimiters."),
+            UnknownKeyword(id) => write!(f, "\"{id}\" is not a keyword id in the schema."),
+            DuplicateKeyword { category, keyword } => write!(
+                f,
+                "Keyword {} appears more than once in category {}.",
+                keyword.name, category.name
+            ),
+            ImpliedKeyword {
+                category,
+                keyword,
+                by,
+            } => write!(
+                f,
+                "Keyword {} in category {} is implied by {} and shouldn't be written too.",
+                keyword.name, category.name, by.name
+            ),
+            MissingEmpty(category) => write!(
+                f,
+                "Category {} has no keywords but is missing the empty marker.",
+                category.name
+            ),
+            MissingCategory(category) => write!(
+                f,
+                "Filename ended before category {} was found.",
+                category.name
+            ),
+            UnexpectedSegment(s) => {
+                write!(f, "Found \"{s}\" after the last category in the schema.")
+            }
+            InvalidValue { category, value } => write!(
+                f,
+                "\"{value}\" is not a valid value for category {}.",
+                category.name
+            ),
+            Requirement(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl StdError for ParseFilenameError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Requirement(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<GenerateFilenameError> for ParseFilenameError {
+    fn from(e: GenerateFilenameError) -> Self {
+        Requirement(e)
+    }
+}
+
+/// decodes the filename portion of the path.
+pub fn parse_path(schema: &Schema, path: &Path) -> Result<ParsedFilename, ParseFilenameError> {
+    let filename = path.file_name().unwrap_or_default();
+    parse(schema, filename.to_str().ok_or(NotUnicode)?)
+}
+
+/// decodes a filename of the form `{id}{delim}{keywords}.{ext}` back into the state that generated it.
+pub fn parse(schema: &Schema, filename: &str) -> Result<ParsedFilename, ParseFilenameError> {
+    // matches how std::path splits extensions so names round trip with what the app generates.
+    // Values like dates can have a `.` too, so a name without an extension is tried whole when the stem doesn't decode.
+    match filename.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => parse_stem(schema, stem, Some(ext))
+            .or_else(|e| parse_stem(schema, filename, None).map_err(|_| e)),
+        _ => parse_stem(schema, filename, None),
+    }
+}
+
+fn parse_stem(
+    schema: &Schema,
+    stem: &str,
+    ext: Option<&str>,
+) -> Result<ParsedFilename, ParseFilenameError> {
+    let (id, body) = stem.split_once(&schema.delim).unwrap_or((stem, ""));
+    if !is_id(id) {
+        return Err(InvalidId(id.to_string()));
+    }
+
+    let segments: Vec<&str> = if body.is_empty() {
+        vec![]
+    } else {
+        body.split(&schema.delim).collect()
+    };
+    let mut segments = segments.into_iter().peekable();
+
+    let mut state = Vec::with_capacity(schema.categories.len());
+    let mut outdated = vec![];
+    for (i, (category, keywords)) in schema.categories.iter().enumerate() {
+        // categories that only apply sometimes are checked once everything is decoded
+        let conditional = !schema.conditions(i).is_empty();
+        if let Some(kind) = &category.value {
+            // a value takes up exactly one segment, which is the empty marker when there isn't one
+            let value = match segments.next() {
+                Some(segment) if segment == schema.empty => vec![],
+                Some(segment) => match value::decode(schema, kind, segment) {
+                    Some(kw) => vec![(kw, true)],
+                    None if segment.is_empty() => return Err(EmptySegment),
+                    None => {
+                        return Err(InvalidValue {
+                            category: Box::new(category.clone()),
+                            value: segment.to_string(),
+                        })
+                    }
+                },
+                None => return Err(MissingCategory(Box::new(category.clone()))),
+            };
+            state.push((category.clone(), value));
+            continue;
+        }
+        let mut selected = vec![false; keywords.len()];
+        let mut count = 0;
+        while let Some(segment) = segments.peek() {
+            let current = keywords.iter().position(|kw| kw.id == *segment);
+            let former = || {
+                (0..keywords.len()).find(|&k| category.former_ids(k).iter().any(|id| id == segment))
+            };
+            if let (None, Some(k)) = (current, former()) {
+                if !selected[k] {
+                    outdated.push((segment.to_string(), keywords[k].clone()));
+                }
+            }
+            match current.or_else(former) {
+                Some(i) if selected[i] => {
+                    return Err(DuplicateKeyword {
+                        category: Box::new(category.clone()),
+                        keyword: keywords[i].clone(),
+                    })
+                }
+                Some(i) => {
+                    selected[i] = true;
+                    count += 1;
+                    segments.next();
+                }
+                None => break,
+            }
+        }
+        // only the most specific keyword is written, since it implies the ones it's nested under
+        for (i, _) in selected.iter().enumerate().filter(|(_, tf)| **tf) {
+            if let Some(j) = (0..keywords.len()).find(|&j| selected[j] && category.is_under(j, i)) {
+                return Err(ImpliedKeyword {
+                    category: Box::new(category.clone()),
+                    keyword: keywords[i].clone(),
+                    by: keywords[j].clone(),
+                });
+            }
+        }
+
+        if count == 0 {
+            match segments.next() {
+                Some(segment) if segment == schema.empty => (),
+                Some(segment) => {
+                    unexpected(schema, segment)?;
+                    if !conditional {
+                        check_requirement(category, count)?;
+                    }
+                    return Err(MissingEmpty(Box::new(category.clone())));
+                }
+                None => return Err(MissingCategory(Box::new(category.clone()))),
+            }
+        }
+        if !conditional {
+            check_requirement(category, count)?;
+        }
+
+        let mut kws: Vec<(Keyword, bool)> = keywords.iter().cloned().zip(selected).collect();
+        for i in 0..kws.len() {
+            if kws[i].1 {
+                set_selected(category, &mut kws, i, true);
+            }
+        }
+        state.push((category.clone(), kws));
+    }
+
+    if let Some(segment) = segments.next() {
+        unexpected(schema, segment)?;
+        return Err(UnexpectedSegment(segment.to_string()));
+    }
+    check_constraints(schema, &state)?;
+
+    Ok(ParsedFilename {
+        id: id.to_string(),
+        state,
+        ext: ext.map(str::to_string),
+        outdated,
+    })
+}
+
+/// returns the most specific error for a segment that doesn't belong where it was found
+fn unexpected(schema: &Schema, segment: &str) -> Result<(), ParseFilenameError> {
+    let known = segment == schema.empty
+        || schema.categories.iter().any(|(cat, kws)| {
+            (0..kws.len())
+                .any(|k| kws[k].id == segment || cat.former_ids(k).iter().any(|id| id == segment))
+        });
+    if segment.is_empty() {
+        Err(EmptySegment)
+    } else if !known {
+        Err(UnknownKeyword(segment.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn parse_examples() {
+    let schema = crate::fixtures::media_people();
+    let parsed = parse(&schema, "ABC123-ph-nate-al.jpg").unwrap();
+    assert_eq!(parsed.id, "ABC123");
+    assert_eq!(parsed.ext, Some("jpg".to_string()));
+    let selected: Vec<&str> = parsed
+        .state
+        .iter()
+        .flat_map(|(_, kws)| {
+            kws.iter()
+                .filter(|(_, tf)| *tf)
+                .map(|(kw, _)| kw.id.as_str())
+        })
+        .collect();
+    assert_eq!(selected, vec!["ph", "nate", "al"]);
+
+    assert!(parse(&schema, "ABC123-v-_").is_ok());
+    assert_eq!(parse(&schema, "ABC123-v-_").unwrap().ext, None);
+}
+
+#[test]
+fn parse_errors() {
+    let schema = crate::fixtures::media_people();
+    assert_eq!(
+        parse(&schema, "IMG_1234.jpg"),
+        Err(InvalidId("IMG_1234".to_string()))
+    );
+    assert_eq!(parse(&schema, "ABC123-ph--nate.jpg"), Err(EmptySegment));
+    assert_eq!(
+        parse(&schema, "ABC123-ph-bob.jpg"),
+        Err(UnknownKeyword("bob".to_string()))
+    );
+    assert_eq!(
+        parse(&schema, "ABC123-ph.jpg"),
+        Err(MissingCategory(Box::new(schema.categories[1].0.clone())))
+    );
+    assert!(matches!(
+        parse(&schema, "ABC123-ph-nate-nate.jpg"),
+        Err(DuplicateKeyword { .. })
+    ));
+    assert!(matches!(
+        parse(&schema, "ABC123-ph-v-_.jpg"),
+        Err(Requirement(GenerateFilenameError::RequirementMismatch {
+            got: 2,
+            ..
+        }))
+    ));
+    assert!(matches!(
+        parse(&schema, "ABC123-nate.jpg"),
+        Err(Requirement(GenerateFilenameError::RequirementMismatch {
+            got: 0,
+            ..
+        }))
+    ));
+    assert_eq!(
+        parse(&schema, "ABC123-ph-_-_.jpg"),
+        Err(UnexpectedSegment("_".to_string()))
+    );
+}
+
+#[test]
+fn parse_constraints() {
+    use super::{generate, is_selectable};
+    use crate::schema::KeywordRef;
+    let input = r#"schema "-" "_"
+  [ category "Media" (exactly 1) ['photo'/'ph', 'video'/'v']
+  , category "Place" (at_most 1) ['indoor'/'in', 'outdoor'/'out']
+  , when 'video' (category "Duration" (exactly 1) ['short', 'long'])
+  , category "Notes" (at_least 0) ['edited'/'ed', 'original'/'orig']
+  ]
+  [ requires 'edited' 'photo', excludes 'edited' 'original' ]"#;
+    let schema = crate::fixtures::schema(input);
+    let decode = |name: &str| parse(&schema, name).map(|parsed| parsed.state);
+
+    assert!(decode("ABC123-v-in-long-_").is_ok());
+    assert!(decode("ABC123-ph-in-_-ed").is_ok());
+    assert!(matches!(
+        decode("ABC123-ph-in-long-_"),
+        Err(Requirement(GenerateFilenameError::Inapplicable { .. }))
+    ));
+    // the category still has its requirement when it applies
+    assert!(matches!(
+        decode("ABC123-v-in-_-_"),
+        Err(Requirement(GenerateFilenameError::RequirementMismatch {
+            got: 0,
+            ..
+        }))
+    ));
+    assert!(matches!(
+        decode("ABC123-v-_-long-ed"),
+        Err(Requirement(GenerateFilenameError::Requires { .. }))
+    ));
+    assert!(matches!(
+        decode("ABC123-ph-_-_-ed-orig"),
+        Err(Requirement(GenerateFilenameError::Excludes(..)))
+    ));
+
+    let mut state = decode("ABC123-ph-in-_-ed").unwrap();
+    assert_eq!(generate(&schema, &state).unwrap(), "ph-in-_-ed");
+    let at = |category, keyword| KeywordRef { category, keyword };
+    assert!(!is_selectable(&schema, &state, at(2, 0)));
+    assert!(!is_selectable(&schema, &state, at(3, 1)));
+    assert!(is_selectable(&schema, &state, at(1, 1)));
+    // picking video makes the durations available
+    state[0].1[0].1 = false;
+    state[0].1[1].1 = true;
+    assert!(is_selectable(&schema, &state, at(2, 0)));
+    assert!(matches!(
+        generate(&schema, &state),
+        Err(GenerateFilenameError::RequirementMismatch { .. })
+    ));
+}
+
+#[test]
+fn parse_values() {
+    use super::generate;
+    let input = r#"schema "-" "_"
+  [ category "Media" (exactly 1) ['photo'/'ph', 'video'/'v']
+  , date "Taken" "%Y%m%d"
+  , number "Rating" 1 5
+  , text "Caption" (max_len 12)
+  ]"#;
+    let schema = crate::fixtures::schema(input);
+    let decode = |name: &str| parse(&schema, name).map(|parsed| parsed.state);
+
+    let state = decode("ABC123-ph-20240301-4-at the beach").unwrap();
+    assert_eq!(state[1].1[0].0.name, "2024-03-01");
+    assert_eq!(state[3].1[0].0.id, "at the beach");
+    assert_eq!(
+        generate(&schema, &state).unwrap(),
+        "ph-20240301-4-at the beach"
+    );
+    // values can be left out
+    let state = decode("ABC123-v-_-_-_").unwrap();
+    assert!(state[1..].iter().all(|(_, kws)| kws.is_empty()));
+
+    assert!(matches!(
+        decode("ABC123-ph-20241301-4-_"),
+        Err(InvalidValue { value, .. }) if value == "20241301"
+    ));
+    assert!(matches!(
+        decode("ABC123-ph-_-6-_"),
+        Err(InvalidValue { value, .. }) if value == "6"
+    ));
+    // numbers are only read the way they're written
+    assert!(matches!(
+        decode("ABC123-ph-_-04-_"),
+        Err(InvalidValue { .. })
+    ));
+    // text that couldn't have been entered, or is a keyword, isn't a value
+    assert!(matches!(
+        decode("ABC123-ph-_-_-a caption that is too long"),
+        Err(InvalidValue { .. })
+    ));
+    assert!(matches!(
+        decode("ABC123-ph-_-_-ph"),
+        Err(InvalidValue { .. })
+    ));
+    assert!(matches!(decode("ABC123-ph-_-_"), Err(MissingCategory(_))));
+
+    // the last `.` is only the extension when the rest of the name decodes
+    let schema =
+        crate::fixtures::with_categories(&[crate::fixtures::MEDIA, r#"date "Taken" "%d.%m.%Y""#]);
+    let parsed = parse(&schema, "ABC123-ph-01.03.2024").unwrap();
+    assert_eq!(parsed.ext, None);
+    assert_eq!(parsed.state[1].1[0].0.name, "2024-03-01");
+    assert_eq!(
+        parse(&schema, "ABC123-ph-01.03.2024.jpg").unwrap().ext,
+        Some("jpg".to_string())
+    );
+    assert_eq!(
+        parse(&schema, "ABC123-ph-01.03"),
+        Err(InvalidValue {
+            category: Box::new(schema.categories[1].0.clone()),
+            value: "01".to_string()
+        })
+    );
+}
+
+#[test]
+fn parse_nested() {
+    use super::{generate, set_selected};
+    let input = r#"schema "-" "_"
+  [ category "Subject" (between 1 2)
+      [ 'animal'/'an' ['dog'/'d', 'bird'/'b' ['owl'/'o']]
+      , 'plant'/'pl'
+      ]
+  ]"#;
+    let schema = crate::fixtures::schema(input);
+    let decode = |name: &str| parse(&schema, name).map(|parsed| parsed.state);
+    let selected = |state: &State| -> Vec<String> {
+        state[0]
+            .1
+            .iter()
+            .filter(|(_, tf)| *tf)
+            .map(|(kw, _)| kw.name.clone())
+            .collect()
+    };
+
+    // the keywords a keyword is nested under are selected along with it
+    let mut state = decode("ABC123-o-pl").unwrap();
+    assert_eq!(selected(&state), vec!["animal", "bird", "owl", "plant"]);
+    assert_eq!(generate(&schema, &state).unwrap(), "o-pl");
+    assert!(decode("ABC123-an").is_ok());
+    assert!(matches!(
+        decode("ABC123-an-d"),
+        Err(ImpliedKeyword { keyword, by, .. }) if keyword.name == "animal" && by.name == "dog"
+    ));
+    // only the most specific keywords count toward the requirement
+    assert!(decode("ABC123-d-o").is_ok());
+    assert!(matches!(
+        decode("ABC123-d-o-pl"),
+        Err(Requirement(GenerateFilenameError::RequirementMismatch {
+            got: 3,
+            ..
+        }))
+    ));
+
+    // deselecting a keyword deselects the ones nested under it
+    let category = state[0].0.clone();
+    set_selected(&category, &mut state[0].1, 0, false);
+    assert_eq!(selected(&state), vec!["plant"]);
+    set_selected(&category, &mut state[0].1, 1, true);
+    assert_eq!(generate(&schema, &state).unwrap(), "d-pl");
+}
+
+#[test]
+fn parse_former_ids() {
+    use super::generate;
+    let input = r#"schema "-" "_"
+  [ category "Media" (exactly 1) ['photo'/'ph' (was 'p'), 'video'/'v']
+  , category "People" (at_least 0) ['nate', 'alice'/'al' (was 'a', 'ali')]
+  ]"#;
+    let schema = crate::fixtures::schema(input);
+
+    let parsed = parse(&schema, "ABC123-p-nate-ali.jpg").unwrap();
+    let outdated: Vec<(&str, &str)> = parsed
+        .outdated
+        .iter()
+        .map(|(id, kw)| (id.as_str(), kw.id.as_str()))
+        .collect();
+    assert_eq!(outdated, vec![("p", "ph"), ("ali", "al")]);
+    // names are always written with the current ids
+    assert_eq!(generate(&schema, &parsed.state).unwrap(), "ph-nate-al");
+    assert_eq!(parse(&schema, "ABC123-ph-al").unwrap().outdated, vec![]);
+    assert!(matches!(
+        parse(&schema, "ABC123-ph-al-a"),
+        Err(DuplicateKeyword { .. })
+    ));
+}
+
+/// generates schemas with every kind of category, then names from them, and checks each name decodes to what made it.
+/// Every case has its own seed so a failure can be reproduced on its own.
+#[test]
+fn roundtrip_generate() {
+    use super::{clear_inapplicable, gen_rand_id, generate, set_selected, value};
+    use crate::{app::to_empty_state, schema::ValueKind};
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+    let mut decoded = 0;
+    for seed in 0..2000 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (delim, empty) = [
+            ("-", "_"),
+            ("_", "-"),
+            ("~", "0"),
+            ("--", "_"),
+            ("-_-", "none"),
+        ]
+        .choose(&mut rng)
+        .unwrap();
+
+        // ids can have characters from the delimiters, which some schemas won't allow
+        let id = |rng: &mut StdRng| -> String {
+            (0..rng.gen_range(1..4))
+                .map(|_| *b"abcxyz-_~".choose(rng).unwrap() as char)
+                .collect()
+        };
+        let mut ids = vec![];
+        let keywords = |rng: &mut StdRng, ids: &mut Vec<String>| -> String {
+            let kws: Vec<String> = (0..rng.gen_range(1..5))
+                .map(|_| {
+                    let kw = id(rng);
+                    ids.push(kw.clone());
+                    // some keywords have keywords nested under them
+                    if rng.gen_bool(0.2) {
+                        let child = format!("{kw}{}", id(rng));
+                        ids.push(child.clone());
+                        format!("'{kw}' ['{child}']")
+                    } else {
+                        format!("'{kw}'")
+                    }
+                })
+                .collect();
+            format!("[{}]", kws.join(", "))
+        };
+        let mut categories = vec![];
+        for c in 0..rng.gen_range(1..5) {
+            let n = rng.gen_range(0..3);
+            let requirement = [
+                format!("exactly {n}"),
+                format!("at_least {n}"),
+                format!("at_most {}", n.max(1)),
+                format!("between {n} {}", n + 1),
+            ]
+            .choose(&mut rng)
+            .unwrap()
+            .clone();
+            let category = match rng.gen_range(0..6) {
+                0 => format!(
+                    r#"date "c{c}" "{}""#,
+                    ["%Y%m%d", "%d.%m.%Y"].choose(&mut rng).unwrap()
+                ),
+                1 => format!(r#"number "c{c}" 1 12"#),
+                2 => format!(r#"text "c{c}" (max_len 6)"#),
+                _ => format!(
+                    r#"category "c{c}" ({requirement}) {}"#,
+                    keywords(&mut rng, &mut ids)
+                ),
+            };
+            // a category can depend on a keyword from one before it
+            match ids.choose(&mut rng) {
+                Some(kw) if rng.gen_bool(0.2) && c > 0 => {
+                    categories.push(format!("when '{kw}' ({category})"))
+                }
+                _ => categories.push(category),
+            }
+        }
+        let constraints: Vec<String> = (0..rng.gen_range(0..3))
+            .filter_map(|_| {
+                let (a, b) = (ids.choose(&mut rng)?, ids.choose(&mut rng)?);
+                let rule = ["requires", "excludes"].choose(&mut rng)?;
+                Some(format!("{rule} '{a}' '{b}'"))
+            })
+            .collect();
+        let input = format!(
+            r#"schema "{delim}" "{empty}" [ {} ] [ {} ]"#,
+            categories.join(", "),
+            constraints.join(", ")
+        );
+        // schemas that can't be decoded unambiguously are rejected before any names are made
+        let Ok(schema) =
+            crate::schema::typecheck::typecheck(crate::schema::parse::parse(&input).unwrap())
+        else {
+            continue;
+        };
+
+        let mut state = to_empty_state(&schema);
+        for (cat, kws) in state.iter_mut() {
+            match &cat.value {
+                Some(ValueKind::Date(format)) if rng.gen_bool(0.7) => {
+                    let date = chrono::NaiveDate::from_ymd_opt(
+                        rng.gen_range(1990..2030),
+                        rng.gen_range(1..13),
+                        rng.gen_range(1..29),
+                    );
+                    *kws = vec![(value::date(format, date.unwrap()), true)];
+                }
+                Some(ValueKind::Number(min, max)) if rng.gen_bool(0.7) => {
+                    *kws = vec![(value::number(rng.gen_range(*min..=*max)), true)];
+                }
+                Some(ValueKind::Text(max)) => {
+                    let input: String = (0..rng.gen_range(0..9))
+                        .map(|_| *b"ab. -_~/".choose(&mut rng).unwrap() as char)
+                        .collect();
+                    *kws = value::text(&schema, &input, *max)
+                        .map(|kw| (kw, true))
+                        .into_iter()
+                        .collect();
+                }
+                Some(_) => (),
+                None => {
+                    for _ in 0..rng.gen_range(0..3) {
+                        let k = rng.gen_range(0..kws.len());
+                        set_selected(cat, kws, k, true);
+                    }
+                }
+            }
+        }
+        clear_inapplicable(&schema, &mut state);
+        // most random selections don't meet the requirements, which generate reports
+        let Ok(name) = generate(&schema, &state) else {
+            continue;
+        };
+
+        let id = gen_rand_id(&mut rng);
+        let ext = [None, Some("jpg"), Some("")].choose(&mut rng).unwrap();
+        let filename = match ext {
+            Some(ext) => format!("{id}{delim}{name}.{ext}"),
+            None => format!("{id}{delim}{name}"),
+        };
+        let parsed = parse(&schema, &filename)
+            .unwrap_or_else(|e| panic!("seed {seed}: failed to decode {filename}: {e:?}\n{input}"));
+        assert_eq!(parsed.id, id, "seed {seed}: {filename}");
+        assert_eq!(parsed.ext.as_deref(), *ext, "seed {seed}: {filename}");
+        // values are compared by what's written since the text that was typed isn't in the name
+        let selected = |state: &State| -> Vec<Vec<String>> {
+            state
+                .iter()
+                .map(|(_, kws)| {
+                    kws.iter()
+                        .filter(|(_, tf)| *tf)
+                        .map(|(kw, _)| kw.id.clone())
+                        .collect()
+                })
+                .collect()
+        };
+        assert_eq!(
+            selected(&parsed.state),
+            selected(&state),
+            "seed {seed}: {filename}\n{input}"
+        );
+        decoded += 1;
+    }
+    // enough of the generated schemas and names are valid to be worth checking
+    assert!(decoded > 200, "only {decoded} names were decoded");
+}
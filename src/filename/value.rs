@@ -0,0 +1,119 @@
+use crate::schema::{analysis::runs_into, Keyword, Schema, ValueKind};
+use chrono::NaiveDate;
+
+/// characters that can't be in a name on at least one of the file systems names are made for.
+/// `.` is left out too so values can't be mistaken for the extension.
+const RESERVED: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|', '.'];
+
+/// how dates are shown and typed, whatever format they have in the filename
+pub const DATE_INPUT_FORMAT: &str = "%Y-%m-%d";
+
+pub fn date(format: &str, date: NaiveDate) -> Keyword {
+    Keyword {
+        name: date.format(DATE_INPUT_FORMAT).to_string(),
+        id: date.format(format).to_string(),
+    }
+}
+
+pub fn number(n: u8) -> Keyword {
+    Keyword {
+        name: n.to_string(),
+        id: n.to_string(),
+    }
+}
+
+/// text with anything that can't be in a filename or would split it into segments taken out, cut to `max` characters.
+/// The text as it was entered is kept as the name.
+pub fn text(schema: &Schema, input: &str, max: u8) -> Option<Keyword> {
+    let allowed: String = input
+        .chars()
+        .filter(|c| !c.is_control() && !RESERVED.contains(c))
+        .collect();
+    let id: String = allowed
+        .replace(&schema.delim, "")
+        .trim()
+        .chars()
+        .take(max as usize)
+        .collect();
+    let id = id.trim_end().to_string();
+    usable(schema, &id).then(|| Keyword {
+        name: input.to_string(),
+        id,
+    })
+}
+
+/// reads a value back from its segment of a filename
+pub fn decode(schema: &Schema, kind: &ValueKind, segment: &str) -> Option<Keyword> {
+    if !usable(schema, segment) {
+        return None;
+    }
+    // only the way the value would have been written is accepted, so names round trip exactly
+    match kind {
+        ValueKind::Date(format) => NaiveDate::parse_from_str(segment, format)
+            .ok()
+            .map(|d| date(format, d))
+            .filter(|kw| kw.id == segment),
+        ValueKind::Number(min, max) => segment
+            .parse::<u8>()
+            .ok()
+            .filter(|n| (min..=max).contains(&n))
+            .map(number)
+            .filter(|kw| kw.id == segment),
+        ValueKind::Text(max) => text(schema, segment, *max).filter(|kw| kw.id == segment),
+    }
+}
+
+/// reads a value the way it's typed by hand, with dates as YYYY-MM-DD
+pub fn from_input(schema: &Schema, kind: &ValueKind, input: &str) -> Option<Keyword> {
+    match kind {
+        ValueKind::Date(format) => NaiveDate::parse_from_str(input.trim(), DATE_INPUT_FORMAT)
+            .ok()
+            .map(|d| date(format, d))
+            .filter(|kw| usable(schema, &kw.id)),
+        ValueKind::Number(..) => decode(schema, kind, input.trim()),
+        ValueKind::Text(max) => text(schema, input, *max),
+    }
+}
+
+/// values can't be mistaken for the empty marker or a keyword, since either could be in the same place in a name
+fn usable(schema: &Schema, id: &str) -> bool {
+    !id.is_empty()
+        && id != schema.empty
+        && !runs_into(id, &schema.delim)
+        && !schema.categories.iter().any(|(cat, kws)| {
+            (0..kws.len()).any(|k| kws[k].id == id || cat.former_ids(k).iter().any(|x| x == id))
+        })
+}
+
+#[test]
+fn values_round_trip() {
+    let schema = crate::fixtures::with_categories(&[crate::fixtures::MEDIA]);
+    let taken = ValueKind::Date("%Y%m%d".to_string());
+
+    let kw = from_input(&schema, &taken, "2024-03-01").unwrap();
+    assert_eq!(
+        (kw.name.as_str(), kw.id.as_str()),
+        ("2024-03-01", "20240301")
+    );
+    assert_eq!(decode(&schema, &taken, &kw.id), Some(kw));
+    assert_eq!(decode(&schema, &taken, "2024031"), None);
+    assert_eq!(from_input(&schema, &taken, "March 1st"), None);
+
+    let rating = ValueKind::Number(1, 5);
+    assert_eq!(from_input(&schema, &rating, " 3 "), Some(number(3)));
+    assert_eq!(from_input(&schema, &rating, "0"), None);
+
+    // anything that can't go in a filename or would split it is taken out
+    let caption = ValueKind::Text(10);
+    let kw = from_input(&schema, &caption, " a/b-c: d.e and more").unwrap();
+    assert_eq!(kw.id, "abc de and");
+    assert_eq!(kw.name, " a/b-c: d.e and more");
+    assert_eq!(
+        decode(&schema, &caption, "abc de and"),
+        Some(text(&schema, "abc de and", 10).unwrap())
+    );
+    // or is rejected when nothing usable is left, or it would be read as something else
+    assert_eq!(from_input(&schema, &caption, "/-"), None);
+    assert_eq!(from_input(&schema, &caption, "_"), None);
+    assert_eq!(from_input(&schema, &caption, "ph"), None);
+}
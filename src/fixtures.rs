@@ -0,0 +1,21 @@
+//! schemas shared by the tests
+
+use crate::schema::{parse, typecheck, Schema};
+
+/// the categories most tests are written against
+pub const MEDIA: &str = r#"category "Media" (exactly 1) ['art', 'photo'/'ph', 'video'/'v']"#;
+pub const PEOPLE: &str = r#"category "People" (at_least 0) ['nate', 'alice'/'al']"#;
+
+/// typechecks a schema that's expected to be valid
+pub fn schema(input: &str) -> Schema {
+    typecheck::typecheck(parse::parse(input).unwrap()).unwrap()
+}
+
+/// a schema delimited by `-` with `_` as the empty marker
+pub fn with_categories(categories: &[&str]) -> Schema {
+    schema(&format!(r#"schema "-" "_" [ {} ]"#, categories.join(", ")))
+}
+
+pub fn media_people() -> Schema {
+    with_categories(&[MEDIA, PEOPLE])
+}
@@ -0,0 +1,267 @@
+use crate::{
+    error::{Error, Result},
+    schema::{Constraint, KeywordRef, Requirement::*, Schema, ValueKind},
+};
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// append-only log of every rename made in a working directory, kept in the directory itself.
+/// It's a dotfile so it's never picked up as a file to tag.
+pub const JOURNAL_FILENAME: &str = ".nametag-journal";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Rename,
+    /// reverses the latest rename that hasn't already been undone
+    Undo,
+}
+
+/// one line of the journal. Paths are filenames relative to the working directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub time: SystemTime,
+    pub op: Op,
+    pub schema_hash: String,
+    pub from: String,
+    pub to: String,
+}
+
+pub fn journal_path(dir: &Path) -> PathBuf {
+    dir.join(JOURNAL_FILENAME)
+}
+
+/// identifies the schema a rename was made with so entries from before a schema change can be told apart.
+/// It's recorded in files that outlive the build that wrote them, so it's a 64-bit FNV-1a hash of
+/// everything that decides how names are written, rather than anything that depends on the compiler or
+/// the fields of the schema types.
+pub fn schema_hash(schema: &Schema) -> String {
+    let hash = canonical(schema)
+        .bytes()
+        .fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+    format!("{hash:016x}")
+}
+
+/// the schema written out one part per line. Changing this changes every hash.
+fn canonical(schema: &Schema) -> String {
+    let mut lines = vec![
+        format!("delim\t{}", schema.delim),
+        format!("empty\t{}", schema.empty),
+    ];
+    for (cat, kws) in &schema.categories {
+        let requirement = match cat.requirement {
+            Exactly(n) => format!("exactly {n}"),
+            AtLeast(n) => format!("at_least {n}"),
+            AtMost(n) => format!("at_most {n}"),
+            Between(min, max) => format!("between {min} {max}"),
+        };
+        let value = match &cat.value {
+            None => "keywords".to_string(),
+            Some(ValueKind::Date(format)) => format!("date {format}"),
+            Some(ValueKind::Number(min, max)) => format!("number {min} {max}"),
+            Some(ValueKind::Text(max)) => format!("text {max}"),
+        };
+        lines.push(format!("category\t{}\t{requirement}\t{value}", cat.name));
+        for (k, kw) in kws.iter().enumerate() {
+            let parent = cat.parent(k).map_or(String::new(), |p| p.to_string());
+            lines.push(format!(
+                "keyword\t{}\t{}\t{parent}\t{}",
+                kw.name,
+                kw.id,
+                cat.former_ids(k).join(",")
+            ));
+        }
+    }
+    let at = |k: &KeywordRef| format!("{} {}", k.category, k.keyword);
+    for constraint in &schema.constraints {
+        lines.push(match constraint {
+            Constraint::Requires(a, b) => format!("requires\t{}\t{}", at(a), at(b)),
+            Constraint::Excludes(a, b) => format!("excludes\t{}\t{}", at(a), at(b)),
+            Constraint::When(a, category) => format!("when\t{}\t{category}", at(a)),
+        });
+    }
+    lines.join("\n")
+}
+
+/// renames a file within the working directory and records it in the journal.
+pub fn rename(dir: &Path, schema: &Schema, from: &Path, to: &Path) -> Result<Entry> {
+    rename_with_hash(dir, &schema_hash(schema), from, to)
+}
+
+/// renames like [`rename`] for when only the hash of the schema is known, like a saved plan
+pub fn rename_with_hash(dir: &Path, schema_hash: &str, from: &Path, to: &Path) -> Result<Entry> {
+    let entry = Entry {
+        time: SystemTime::now(),
+        op: Op::Rename,
+        schema_hash: schema_hash.to_string(),
+        from: super::name_of(from),
+        to: super::name_of(to),
+    };
+    super::rename(from, to)?;
+    append(dir, &entry)?;
+    Ok(entry)
+}
+
+/// moves the file back to its name before the entry was applied and records that it was undone.
+pub fn undo(dir: &Path, entry: &Entry) -> Result<Entry> {
+    let undo = Entry {
+        time: SystemTime::now(),
+        op: Op::Undo,
+        schema_hash: entry.schema_hash.clone(),
+        from: entry.to.clone(),
+        to: entry.from.clone(),
+    };
+    super::rename(&dir.join(&undo.from), &dir.join(&undo.to))?;
+    append(dir, &undo)?;
+    Ok(undo)
+}
+
+pub fn read(dir: &Path) -> Result<Vec<Entry>> {
+    let contents = match fs::read_to_string(journal_path(dir)) {
+        Ok(contents) => contents,
+        // nothing has been renamed yet
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(Error::JournalRead(e)),
+    };
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| parse_entry(line).ok_or(Error::MalformedJournal(i + 1)))
+        .collect()
+}
+
+/// the renames that are still in effect, oldest first. Each undo cancels the latest rename before it.
+pub fn active(entries: &[Entry]) -> Vec<Entry> {
+    let mut stack = vec![];
+    for entry in entries {
+        match entry.op {
+            Op::Rename => stack.push(entry.clone()),
+            Op::Undo => {
+                stack.pop();
+            }
+        }
+    }
+    stack
+}
+
+fn append(dir: &Path, entry: &Entry) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(journal_path(dir))
+        .map_err(Error::JournalWrite)?;
+    writeln!(file, "{}", format_entry(entry)).map_err(Error::JournalWrite)
+}
+
+fn format_entry(entry: &Entry) -> String {
+    let op = match entry.op {
+        Op::Rename => "rename",
+        Op::Undo => "undo",
+    };
+    format!(
+        "{}\t{op}\t{}\t{}\t{}",
+        humantime::format_rfc3339_seconds(entry.time),
+        entry.schema_hash,
+        escape(&entry.from),
+        escape(&entry.to)
+    )
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut fields = line.split('\t');
+    let time = humantime::parse_rfc3339(fields.next()?).ok()?;
+    let op = match fields.next()? {
+        "rename" => Op::Rename,
+        "undo" => Op::Undo,
+        _ => return None,
+    };
+    let schema_hash = fields.next()?.to_string();
+    let from = unescape(fields.next()?)?;
+    let to = unescape(fields.next()?)?;
+    if fields.next().is_some() {
+        return None;
+    }
+    Some(Entry {
+        time,
+        op,
+        schema_hash,
+        from,
+        to,
+    })
+}
+
+/// filenames can technically contain the tabs and newlines the journal format is delimited by
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> Option<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                '\\' => out.push('\\'),
+                't' => out.push('\t'),
+                'n' => out.push('\n'),
+                _ => return None,
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+fn entry(op: Op, from: &str, to: &str) -> Entry {
+    Entry {
+        time: humantime::parse_rfc3339("2024-05-01T12:00:00Z").unwrap(),
+        op,
+        schema_hash: "0123456789abcdef".to_string(),
+        from: from.to_string(),
+        to: to.to_string(),
+    }
+}
+
+#[test]
+fn entry_roundtrip() {
+    for e in [
+        entry(Op::Rename, "IMG_0001.jpg", "ABC123-ph-nate.jpg"),
+        entry(Op::Undo, "odd\tname\\with\nescapes", "b"),
+    ] {
+        assert_eq!(parse_entry(&format_entry(&e)), Some(e));
+    }
+    assert_eq!(parse_entry("not a journal line"), None);
+}
+
+#[test]
+fn active_renames() {
+    let a = entry(Op::Rename, "a", "A");
+    let b = entry(Op::Rename, "b", "B");
+    let c = entry(Op::Rename, "c", "C");
+    let undo = |e: &Entry| entry(Op::Undo, &e.to, &e.from);
+    assert_eq!(
+        active(&[a.clone(), b.clone(), undo(&b), c.clone()]),
+        vec![a.clone(), c.clone()]
+    );
+    assert_eq!(active(&[a.clone(), b.clone(), undo(&b), undo(&a)]), vec![]);
+}
+
+#[test]
+fn schema_hash_is_stable() {
+    // recorded hashes have to keep matching, so this only changes along with `canonical` on purpose
+    let schema = crate::fixtures::media_people();
+    assert_eq!(schema_hash(&schema), "6dd133c2059d5523");
+    let mut renamed = schema.clone();
+    renamed.categories[1].1[0].id = "n".to_string();
+    assert_ne!(schema_hash(&renamed), schema_hash(&schema));
+}
@@ -1,17 +1,29 @@
+pub mod journal;
+pub mod plan;
+
 use crate::{
     error::{Error, Result},
+    filename,
     schema::{self, Schema},
 };
+use rand::rngs::ThreadRng;
 use std::{
-    fs,
+    collections::HashMap,
+    fs, io,
     path::{Path, PathBuf},
 };
+use tracing::info;
+
+pub const SCHEMA_FILENAME: &str = "schema.q";
+
+/// the schema file for a working directory
+pub fn schema_path(dir: &Path) -> PathBuf {
+    dir.join(SCHEMA_FILENAME)
+}
 
 pub fn read_schema_file(path: &Path) -> Result<Schema> {
     let contents = fs::read_to_string(path).map_err(Error::FailedToReadContents)?;
-    let parsed = schema::parse::parse(&contents)?;
-    let schema = schema::typecheck::typecheck(parsed)?;
-    Ok(schema)
+    schema::load(&contents, path).map_err(Error::Schema)
 }
 
 /// collects filenames of all non-directory entries in the given directory.
@@ -29,6 +41,191 @@ pub fn collect_filenames(dir: &dyn AsRef<Path>) -> Result<Vec<PathBuf>> {
     Ok(files)
 }
 
+/// the last part of a path, which is all that's shown or recorded of files in the working directory
+pub(crate) fn name_of(path: &Path) -> String {
+    path.file_name()
+        .map_or(String::new(), |os| os.to_string_lossy().to_string())
+}
+
+/// collects the files in the working directory that can be tagged, sorted by name.
+/// dotfiles, the schema file, and plans saved with `--plan` are skipped.
+pub fn collect_taggable(dir: &dyn AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = collect_filenames(dir)?
+        .into_iter()
+        .filter(|path| {
+            // since this string representation is only used to rule out certain files, it's safe to use even in cross-platform builds
+            let filename = path
+                .file_name()
+                .map_or(String::new(), |fname| fname.to_string_lossy().to_string());
+            !filename.starts_with('.') && filename != SCHEMA_FILENAME && !plan::is_plan(path)
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// renames the file, refusing to overwrite anything already at the destination.
+pub fn rename(from: &Path, to: &Path) -> Result<()> {
+    if from != to {
+        move_without_replacing(from, to)?;
+    }
+    info!(
+        "{} →  {}",
+        from.file_name()
+            .map_or("old".into(), |os| os.to_string_lossy()),
+        to.file_name()
+            .map_or("new".into(), |os| os.to_string_lossy())
+    );
+    Ok(())
+}
+
+/// std::fs::rename silently replaces the destination on unix, and checking for it first leaves a gap where
+/// another file can appear. Linking the new name fails if anything is there, even a dangling link.
+fn move_without_replacing(from: &Path, to: &Path) -> Result<()> {
+    match fs::hard_link(from, to) {
+        Ok(()) => fs::remove_file(from).map_err(|e| {
+            // put things back the way they were so the file doesn't end up under both names
+            let _ = fs::remove_file(to);
+            Error::FailedRename(e)
+        }),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            Err(Error::RenameCollision(to.to_path_buf()))
+        }
+        // some file systems, like FAT, don't have hard links, so the best that can be done is checking first
+        Err(_) => {
+            if fs::symlink_metadata(to).is_ok() {
+                return Err(Error::RenameCollision(to.to_path_buf()));
+            }
+            fs::rename(from, to).map_err(Error::FailedRename)
+        }
+    }
+}
+
+/// the file ids at the start of the names in a directory, and how many files have each one.
+/// It's read once and kept up to date as files are renamed, so a batch doesn't rescan the directory for every file.
+pub struct Ids {
+    delim: String,
+    counts: HashMap<String, usize>,
+}
+
+impl Ids {
+    pub fn read(dir: &Path, delim: &str) -> Result<Self> {
+        let mut ids = Ids {
+            delim: delim.to_string(),
+            counts: HashMap::new(),
+        };
+        for path in collect_filenames(&dir)? {
+            ids.add(&path);
+        }
+        Ok(ids)
+    }
+
+    /// whether any file other than the one given has the id
+    pub fn taken(&self, id: &str, file: &Path) -> bool {
+        let own = usize::from(self.id_of(file) == Some(id));
+        self.counts.get(id).is_some_and(|&n| n > own)
+    }
+
+    /// the id a file keeps when it's renamed. Ids must stay unique, so it gets a new one if it doesn't
+    /// have one or another file in the directory already has it.
+    pub fn unique_id(
+        &self,
+        file: &Path,
+        id: Option<String>,
+        rng: &mut ThreadRng,
+    ) -> Result<String> {
+        match id {
+            Some(id) if !self.taken(&id, file) => return Ok(id),
+            Some(id) => info!("File id {id} is already in use. Generating a new one."),
+            None => {}
+        }
+        filename::gen_unique_id(rng, |id| self.taken(id, file)).ok_or(Error::NoFreeId)
+    }
+
+    /// keeps the ids up to date with a file that was renamed
+    pub fn renamed(&mut self, from: &Path, to: &Path) {
+        if let Some(n) = self.id_of(from).and_then(|id| self.counts.get_mut(id)) {
+            *n -= 1;
+        }
+        self.add(to);
+    }
+
+    fn add(&mut self, path: &Path) {
+        if let Some(id) = self.id_of(path) {
+            *self.counts.entry(id.to_string()).or_default() += 1;
+        }
+    }
+
+    fn id_of<'a>(&self, path: &'a Path) -> Option<&'a str> {
+        let (id, _) = path
+            .file_name()?
+            .to_str()?
+            .split_once(self.delim.as_str())?;
+        filename::is_id(id).then_some(id)
+    }
+}
+
+/// creates an empty directory for tests that need to touch the file system
+#[cfg(test)]
+pub fn test_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "nametag-test-{}",
+        filename::gen_rand_id(&mut rand::thread_rng())
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn rename_refuses_to_overwrite() {
+    let dir = test_dir();
+    let from = dir.join("IMG_0001.jpg");
+    let to = dir.join("ABC123-ph.jpg");
+    fs::write(&from, "new").unwrap();
+    fs::write(&to, "existing").unwrap();
+
+    assert!(matches!(rename(&from, &to), Err(Error::RenameCollision(p)) if p == to));
+    assert_eq!(fs::read_to_string(&to).unwrap(), "existing");
+    assert!(from.exists());
+
+    // even a link to nothing is in the way
+    #[cfg(unix)]
+    {
+        let link = dir.join("DEF456-ph.jpg");
+        std::os::unix::fs::symlink(dir.join("missing"), &link).unwrap();
+        assert!(matches!(rename(&from, &link), Err(Error::RenameCollision(p)) if p == link));
+        assert!(from.exists());
+        fs::remove_file(link).unwrap();
+    }
+
+    // renaming a file to its own name isn't a collision
+    assert!(rename(&to, &to).is_ok());
+
+    // a file that would share an id with another one gets a new id instead
+    let rng = &mut rand::thread_rng();
+    let mut ids = Ids::read(&dir, "-").unwrap();
+    assert!(ids.taken("ABC123", &from) && !ids.taken("ABC123", &to));
+    let id = ids
+        .unique_id(&from, Some("ABC123".to_string()), rng)
+        .unwrap();
+    assert!(filename::is_id(&id) && id != "ABC123");
+    assert_eq!(
+        ids.unique_id(&to, Some("ABC123".to_string()), rng).unwrap(),
+        "ABC123"
+    );
+    assert!(filename::is_id(&ids.unique_id(&from, None, rng).unwrap()));
+
+    // giving up is better than hanging when no id is free
+    assert_eq!(filename::gen_unique_id(rng, |_| true), None);
+
+    // renames are kept track of without reading the directory again
+    let renamed = dir.join("DEF456-ph.jpg");
+    ids.renamed(&to, &renamed);
+    assert!(!ids.taken("ABC123", &from) && ids.taken("DEF456", &from));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
 #[cfg(test)]
 /// used to test file system limitations for cross-platform compatibility
 mod limitations {
@@ -47,7 +244,7 @@ mod limitations {
             "子曰。學而時習之、不亦說乎。有朋自遠方來、不亦樂乎。人不知而不慍、不亦君子乎。有子曰。其爲人也孝弟、而好犯上者、鮮矣。不好犯上、而好作亂者、未之有也。君子務本、本立而--.jpg",
         ];
         for s in utf8_byte_255 {
-            assert_eq!(255, s.bytes().len());
+            assert_eq!(255, s.len());
         }
 
         let utf16_byte_254 = [
@@ -0,0 +1,239 @@
+use super::journal;
+use crate::{
+    error::{Error, Result},
+    schema::Schema,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    result::Result as StdResult,
+};
+
+/// renames worked out ahead of time so they can be looked over, saved, and applied later.
+/// Each one remembers the size and modification time of its file so changes since planning are caught.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    /// always `PLAN_VERSION`. It's written first so plans can be told apart from other json by their first bytes.
+    pub nametag_plan: u32,
+    pub working_dir: PathBuf,
+    /// the schema the new names were made with, recorded in the journal when the plan is applied
+    pub schema_hash: String,
+    pub renames: Vec<Rename>,
+}
+
+pub const PLAN_VERSION: u32 = 1;
+
+/// paths are filenames relative to the working directory
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rename {
+    pub from: String,
+    pub size: u64,
+    pub modified: String,
+    #[serde(flatten)]
+    pub status: Status,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Status {
+    Ready {
+        to: String,
+    },
+    /// no new name could be made. These are skipped when the plan is applied.
+    Failed {
+        error: String,
+    },
+}
+
+impl Plan {
+    pub fn new(working_dir: &Path, schema: &Schema) -> Self {
+        Plan {
+            nametag_plan: PLAN_VERSION,
+            working_dir: working_dir.to_path_buf(),
+            schema_hash: journal::schema_hash(schema),
+            renames: vec![],
+        }
+    }
+
+    /// adds the file with its new name, or the reason it doesn't have one
+    pub fn push(&mut self, from: &Path, to: StdResult<&Path, String>) -> Result<()> {
+        let (size, modified) = fingerprint(from)?;
+        let status = match to {
+            Ok(to) => Status::Ready {
+                to: super::name_of(to),
+            },
+            Err(error) => Status::Failed { error },
+        };
+        self.renames.push(Rename {
+            from: super::name_of(from),
+            size,
+            modified,
+            status,
+        });
+        Ok(())
+    }
+
+    pub fn failed(&self) -> usize {
+        self.renames
+            .iter()
+            .filter(|r| matches!(r.status, Status::Failed { .. }))
+            .count()
+    }
+
+    /// the files that were changed, moved, or deleted since the plan was made
+    pub fn stale(&self) -> Vec<&Rename> {
+        self.renames
+            .iter()
+            .filter(|r| matches!(r.status, Status::Ready { .. }))
+            .filter(|r| {
+                fingerprint(&self.working_dir.join(&r.from)).map_or(true, |(size, modified)| {
+                    size != r.size || modified != r.modified
+                })
+            })
+            .collect()
+    }
+
+    /// checks that every new name is free by the time its rename is made. A name can be taken by a file
+    /// that's renamed away earlier in the plan, but not by any other file, or by another rename in the plan.
+    pub fn check_targets(&self) -> Result<()> {
+        let mut freed = HashSet::new();
+        let mut taken = HashSet::new();
+        for rename in &self.renames {
+            let Status::Ready { to } = &rename.status else {
+                continue;
+            };
+            if !taken.insert(to) {
+                return Err(Error::PlanTargetsCollide(to.clone()));
+            }
+            let path = self.working_dir.join(to);
+            if *to != rename.from && !freed.contains(to) && fs::symlink_metadata(&path).is_ok() {
+                return Err(Error::RenameCollision(path));
+            }
+            freed.insert(&rename.from);
+        }
+        Ok(())
+    }
+
+    /// makes every rename that's ready, but only if none of their files have changed since planning
+    /// and every new name is free, so a plan is never left partly applied by a file in the way
+    pub fn apply(&self) -> Result<()> {
+        let stale = self.stale().len();
+        if stale > 0 {
+            return Err(Error::StalePlan(stale));
+        }
+        self.check_targets()?;
+        for rename in &self.renames {
+            if let Status::Ready { to } = &rename.status {
+                journal::rename_with_hash(
+                    &self.working_dir,
+                    &self.schema_hash,
+                    &self.working_dir.join(&rename.from),
+                    &self.working_dir.join(to),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        // a plan is only strings and numbers, so there's nothing that can fail to serialize
+        serde_json::to_string_pretty(self).expect("plan serializes to json")
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json() + "\n").map_err(Error::PlanWrite)
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(Error::PlanRead)?;
+        serde_json::from_str(&contents).map_err(Error::MalformedPlan)
+    }
+}
+
+/// plans can be saved anywhere, including the working directory, where they mustn't be tagged.
+/// Only the start of the file is read, since this is checked for every json file in a directory.
+pub fn is_plan(path: &Path) -> bool {
+    if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+        return false;
+    }
+    let mut start = [0; 64];
+    let Ok(read) = fs::File::open(path).and_then(|mut file| file.read(&mut start)) else {
+        return false;
+    };
+    let start = String::from_utf8_lossy(&start[..read]);
+    start
+        .trim_start()
+        .strip_prefix('{')
+        .is_some_and(|rest| rest.trim_start().starts_with("\"nametag_plan\""))
+}
+
+fn fingerprint(path: &Path) -> Result<(u64, String)> {
+    let metadata = fs::metadata(path).map_err(Error::PathErr)?;
+    let modified = metadata.modified().map_err(Error::PathErr)?;
+    Ok((
+        metadata.len(),
+        humantime::format_rfc3339_nanos(modified).to_string(),
+    ))
+}
+
+#[test]
+fn plan_round_trip() {
+    let schema = crate::fixtures::media_people();
+    let dir = super::test_dir();
+    let (a, b) = (dir.join("IMG_0001.jpg"), dir.join("IMG_0002.jpg"));
+    fs::write(&a, "a").unwrap();
+    fs::write(&b, "b").unwrap();
+
+    let mut plan = Plan::new(&dir, &schema);
+    plan.push(&a, Ok(&dir.join("ABC123-ph.jpg"))).unwrap();
+    plan.push(&b, Err("no keyword in Media".to_string()))
+        .unwrap();
+    let path = dir.join("plan.json");
+    plan.write(&path).unwrap();
+    let read = Plan::read(&path).unwrap();
+    assert_eq!(read, plan);
+    assert_eq!(read.failed(), 1);
+    assert_eq!(
+        super::collect_taggable(&dir).unwrap(),
+        vec![a.clone(), b.clone()]
+    );
+    // other json is left alone, even if it looks like a plan
+    let data = dir.join("data.json");
+    fs::write(&data, plan.to_json().replace("nametag_plan", "version")).unwrap();
+    assert!(!is_plan(&data));
+    assert!(matches!(Plan::read(&data), Err(Error::MalformedPlan(_))));
+    fs::remove_file(data).unwrap();
+
+    // failed renames aren't checked, since they aren't applied
+    fs::write(&b, "changed").unwrap();
+    assert!(read.stale().is_empty());
+    fs::write(&a, "changed").unwrap();
+    assert!(matches!(read.apply(), Err(Error::StalePlan(1))));
+    assert!(a.exists());
+
+    // nothing is renamed when two files would get the same name, or a name that's taken
+    let mut plan = Plan::new(&dir, &schema);
+    plan.push(&a, Ok(&dir.join("ABC123-ph.jpg"))).unwrap();
+    plan.push(&b, Ok(&dir.join("ABC123-ph.jpg"))).unwrap();
+    assert!(matches!(plan.apply(), Err(Error::PlanTargetsCollide(to)) if to == "ABC123-ph.jpg"));
+    let mut plan = Plan::new(&dir, &schema);
+    plan.push(&a, Ok(&dir.join("ABC123-ph.jpg"))).unwrap();
+    plan.push(&b, Ok(&path)).unwrap();
+    assert!(matches!(plan.apply(), Err(Error::RenameCollision(to)) if to == path));
+    assert!(a.exists() && b.exists());
+
+    // a name can be reused once the file that had it is renamed
+    let mut plan = Plan::new(&dir, &schema);
+    plan.push(&a, Ok(&dir.join("ABC123-ph.jpg"))).unwrap();
+    plan.push(&b, Ok(&a)).unwrap();
+    plan.apply().unwrap();
+    assert!(dir.join("ABC123-ph.jpg").exists());
+    assert_eq!(fs::read_to_string(&a).unwrap(), "changed");
+    assert!(dir.join("ABC123-ph.jpg").exists());
+    assert_eq!(journal::read(&dir).unwrap().len(), 2);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -2,6 +2,11 @@
 use std::process::ExitCode;
 
 fn main() -> ExitCode {
+    // with arguments it was started from a command line, which should see what the subcommands print
+    #[cfg(all(windows, not(debug_assertions)))]
+    if std::env::args_os().len() > 1 {
+        attach_console();
+    }
     match nametag::run() {
         Err(e) => {
             eprintln!("{}", e);
@@ -10,3 +15,18 @@ fn main() -> ExitCode {
         Ok(()) => ExitCode::SUCCESS,
     }
 }
+
+/// windows_subsystem leaves release builds without a console, so output goes to the one that started
+/// them, if there is one. Started from Explorer there isn't, and this does nothing.
+#[cfg(all(windows, not(debug_assertions)))]
+fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // SAFETY: AttachConsole takes no pointers and only fails when there's no console to attach to
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{
+    app,
+    filename::{self, value},
+    schema::{Keyword, Schema},
+    State,
+};
+use std::{error::Error as StdError, fmt};
+use MigrateError::*;
+
+/// how the keywords in names made with one schema are carried over to another.
+/// Categories and keywords are otherwise matched by name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// `Old=New` moves everything in a category to the one with the new name
+    Category { from: String, to: String },
+    /// `Old:keyword=New:keyword` replaces a keyword, or drops it when nothing follows the `=`
+    Keyword {
+        from: (String, String),
+        to: Option<(String, String)>,
+    },
+    /// `Category=keyword` is selected in categories nothing else was carried over to
+    Default { category: String, keyword: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MigrateError {
+    UnknownCategory(String),
+    UnknownKeyword {
+        category: String,
+        keyword: String,
+    },
+    /// a selected keyword that nothing in the new schema matches
+    Unmapped {
+        category: String,
+        keyword: String,
+    },
+    InvalidValue {
+        category: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for MigrateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnknownCategory(name) => write!(f, "Unknown category \"{name}\""),
+            UnknownKeyword { category, keyword } => {
+                write!(f, "Category {category} has no keyword \"{keyword}\"")
+            }
+            Unmapped { category, keyword } => write!(
+                f,
+                "Keyword {keyword} in category {category} has no place in the new schema. Map it with --map."
+            ),
+            InvalidValue { category, value } => write!(
+                f,
+                "\"{value}\" is not a valid value for category {category} in the new schema"
+            ),
+        }
+    }
+}
+
+impl StdError for MigrateError {}
+
+/// parses a `--map` argument: `Old=New` for categories, or `Old:keyword=New:keyword` for keywords
+pub fn parse_map(s: &str) -> Result<Rule, String> {
+    let (from, to) = s
+        .split_once('=')
+        .ok_or("expected OLD=NEW like Media=Kind or Media:photo=Kind:picture")?;
+    match (from.split_once(':'), to.split_once(':')) {
+        (None, None) if !to.is_empty() => Ok(Rule::Category {
+            from: from.to_string(),
+            to: to.to_string(),
+        }),
+        (Some(from), to) if to.is_some() || s.ends_with('=') => Ok(Rule::Keyword {
+            from: (from.0.to_string(), from.1.to_string()),
+            to: to.map(|(c, k)| (c.to_string(), k.to_string())),
+        }),
+        _ => Err(
+            "a category can only be mapped to a category, and a keyword to a keyword".to_string(),
+        ),
+    }
+}
+
+/// parses a `--default` argument: `Category=keyword`
+pub fn parse_default(s: &str) -> Result<Rule, String> {
+    let (category, keyword) = s.split_once('=').ok_or("expected CATEGORY=KEYWORD")?;
+    Ok(Rule::Default {
+        category: category.to_string(),
+        keyword: keyword.to_string(),
+    })
+}
+
+/// checks that every category and keyword the rules name is in the schema it belongs to
+pub fn check_rules(from: &Schema, to: &Schema, rules: &[Rule]) -> Result<(), MigrateError> {
+    for rule in rules {
+        match rule {
+            Rule::Category { from: old, to: new } => {
+                find_category(from, old)?;
+                find_category(to, new)?;
+            }
+            Rule::Keyword { from: old, to: new } => {
+                find_keyword(from, &old.0, &old.1)?;
+                if let Some(new) = new {
+                    find_keyword(to, &new.0, &new.1)?;
+                }
+            }
+            Rule::Default { category, keyword } => {
+                find_keyword(to, category, keyword)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// carries the state of a name made with another schema over to the `to` schema
+pub fn migrate(state: &State, to: &Schema, rules: &[Rule]) -> Result<State, MigrateError> {
+    let mut migrated = app::to_empty_state(to);
+    for (cat, kws) in state {
+        let target = rules
+            .iter()
+            .find_map(|rule| match rule {
+                Rule::Category { from, to } if *from == cat.name => Some(to.as_str()),
+                _ => None,
+            })
+            .unwrap_or(&cat.name);
+        let unmapped = |kw: &Keyword| Unmapped {
+            category: cat.name.clone(),
+            keyword: kw.name.clone(),
+        };
+
+        if cat.value.is_some() {
+            for (kw, _) in kws.iter().filter(|(_, tf)| *tf) {
+                let (new_cat, new_kws) = migrated
+                    .iter_mut()
+                    .find(|(c, _)| c.name == target)
+                    .ok_or_else(|| unmapped(kw))?;
+                let kind = new_cat.value.as_ref().ok_or_else(|| unmapped(kw))?;
+                // values are carried over the way they'd be entered, so dates can change format
+                let value = value::from_input(to, kind, &kw.name).ok_or_else(|| InvalidValue {
+                    category: new_cat.name.clone(),
+                    value: kw.name.clone(),
+                })?;
+                *new_kws = vec![(value, true)];
+            }
+            continue;
+        }
+
+        for i in filename::written(cat, kws) {
+            let kw = &kws[i].0;
+            let rule = rules.iter().find_map(|rule| match rule {
+                Rule::Keyword { from, to } if from.0 == cat.name && kw.matches(&from.1) => Some(to),
+                _ => None,
+            });
+            let (category, keyword) = match rule {
+                Some(Some((category, keyword))) => (category.as_str(), keyword.as_str()),
+                // dropped
+                Some(None) => continue,
+                None => (target, kw.name.as_str()),
+            };
+            select(&mut migrated, category, keyword).ok_or_else(|| unmapped(kw))?;
+        }
+    }
+
+    for rule in rules {
+        if let Rule::Default { category, keyword } = rule {
+            let empty = migrated
+                .iter()
+                .find(|(c, _)| c.name == *category)
+                .is_some_and(|(_, kws)| kws.iter().all(|(_, tf)| !tf));
+            if empty {
+                select(&mut migrated, category, keyword);
+            }
+        }
+    }
+    Ok(migrated)
+}
+
+/// selects the keyword along with the ones it's nested under, if the category has it
+fn select(state: &mut State, category: &str, keyword: &str) -> Option<()> {
+    let (cat, kws) = state.iter_mut().find(|(c, _)| c.name == category)?;
+    let i = kws.iter().position(|(kw, _)| kw.matches(keyword))?;
+    filename::set_selected(cat, kws, i, true);
+    Some(())
+}
+
+fn find_category<'a>(schema: &'a Schema, name: &str) -> Result<&'a [Keyword], MigrateError> {
+    schema
+        .categories
+        .iter()
+        .find(|(cat, _)| cat.name == name)
+        .map(|(_, kws)| kws.as_slice())
+        .ok_or_else(|| UnknownCategory(name.to_string()))
+}
+
+fn find_keyword(schema: &Schema, category: &str, keyword: &str) -> Result<(), MigrateError> {
+    if find_category(schema, category)?
+        .iter()
+        .any(|kw| kw.matches(keyword))
+    {
+        Ok(())
+    } else {
+        Err(UnknownKeyword {
+            category: category.to_string(),
+            keyword: keyword.to_string(),
+        })
+    }
+}
+
+#[test]
+fn migrate_states() {
+    use crate::{
+        filename::{generate, parse::parse},
+        fixtures::{with_categories, MEDIA, PEOPLE},
+    };
+    let old = with_categories(&[MEDIA, PEOPLE, r#"date "Taken" "%Y%m%d""#]);
+    // categories are reordered and renamed, an id changes, and a category is added
+    let new = with_categories(&[
+        r#"date "Taken" "%d.%m.%Y""#,
+        r#"category "Kind" (exactly 1) ['art', 'photo'/'p', 'clip'/'c']"#,
+        r#"category "Place" (exactly 1) ['home', 'away']"#,
+        PEOPLE,
+    ]);
+    let rules = vec![
+        parse_map("Media=Kind").unwrap(),
+        parse_map("Media:video=Kind:clip").unwrap(),
+        parse_map("People:nate=").unwrap(),
+        parse_default("Place=home").unwrap(),
+    ];
+    check_rules(&old, &new, &rules).unwrap();
+    let run = |name: &str| {
+        let state = parse(&old, name).unwrap().state;
+        migrate(&state, &new, &rules).map(|state| generate(&new, &state).unwrap())
+    };
+
+    assert_eq!(
+        run("ABC123-ph-nate-al-20240301"),
+        Ok("01.03.2024-p-home-al".to_string())
+    );
+    assert_eq!(run("ABC123-v-_-_"), Ok("_-c-home-_".to_string()));
+
+    assert_eq!(
+        check_rules(&old, &new, &[parse_map("Media:drawing=").unwrap()]),
+        Err(UnknownKeyword {
+            category: "Media".to_string(),
+            keyword: "drawing".to_string()
+        })
+    );
+    let state = parse(&old, "ABC123-v-_-_").unwrap().state;
+    assert_eq!(
+        migrate(&state, &new, &[]),
+        Err(Unmapped {
+            category: "Media".to_string(),
+            keyword: "video".to_string()
+        })
+    );
+    assert!(parse_map("Media=Kind:clip").is_err());
+    assert!(parse_map("Media").is_err());
+}
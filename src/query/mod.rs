@@ -0,0 +1,244 @@
+pub mod parse;
+
+use crate::{filename, schema::Schema, State};
+use std::{error::Error as StdError, fmt, path::PathBuf};
+use Query::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Query {
+    /// matches when the keyword is selected. Without a category any category may contain it.
+    Keyword {
+        category: Option<String>,
+        keyword: String,
+    },
+    /// matches when the number of selected keywords in the category satisfies the comparison.
+    /// Keywords implied by one nested under them aren't counted.
+    Count {
+        category: String,
+        cmp: Comparison,
+        n: usize,
+    },
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn apply(self, got: usize, n: usize) -> bool {
+        match self {
+            Self::Eq => got == n,
+            Self::Lt => got < n,
+            Self::Le => got <= n,
+            Self::Gt => got > n,
+            Self::Ge => got >= n,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryError {
+    UnexpectedInput(String),
+    UnknownCategory(String),
+    UnknownKeyword {
+        category: Option<String>,
+        keyword: String,
+    },
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedInput(input) => write!(f, "Unexpected input in query: {input}"),
+            Self::UnknownCategory(name) => write!(f, "Unknown category \"{name}\"."),
+            Self::UnknownKeyword {
+                category: Some(category),
+                keyword,
+            } => write!(f, "Category {category} has no keyword \"{keyword}\"."),
+            Self::UnknownKeyword {
+                category: None,
+                keyword,
+            } => write!(f, "No category has the keyword \"{keyword}\"."),
+        }
+    }
+}
+
+impl StdError for QueryError {}
+
+/// parses the query and checks that everything it references exists in the schema.
+pub fn compile(schema: &Schema, input: &str) -> Result<Query, QueryError> {
+    let query = parse::parse(input)?;
+    query.check(schema)?;
+    Ok(query)
+}
+
+/// keeps the files whose names decode against the schema and match the query.
+pub fn filter(schema: &Schema, query: &Query, files: Vec<PathBuf>) -> Vec<PathBuf> {
+    files
+        .into_iter()
+        .filter(|path| {
+            filename::parse::parse_path(schema, path)
+                .is_ok_and(|parsed| query.matches(&parsed.state))
+        })
+        .collect()
+}
+
+impl Query {
+    /// rejects queries that reference categories or keywords the schema doesn't have.
+    pub fn check(&self, schema: &Schema) -> Result<(), QueryError> {
+        match self {
+            Keyword { category, keyword } => {
+                let mut categories = schema
+                    .categories
+                    .iter()
+                    .filter(|(cat, _)| category.iter().all(|name| cat.name == *name))
+                    .peekable();
+                if let (Some(name), None) = (category, categories.peek()) {
+                    return Err(QueryError::UnknownCategory(name.clone()));
+                }
+                if categories.any(|(_, kws)| kws.iter().any(|kw| kw.matches(keyword))) {
+                    Ok(())
+                } else {
+                    Err(QueryError::UnknownKeyword {
+                        category: category.clone(),
+                        keyword: keyword.clone(),
+                    })
+                }
+            }
+            Count { category, .. } => {
+                if schema
+                    .categories
+                    .iter()
+                    .any(|(cat, _)| cat.name == *category)
+                {
+                    Ok(())
+                } else {
+                    Err(QueryError::UnknownCategory(category.clone()))
+                }
+            }
+            Not(q) => q.check(schema),
+            And(l, r) | Or(l, r) => {
+                l.check(schema)?;
+                r.check(schema)
+            }
+        }
+    }
+
+    pub fn matches(&self, state: &State) -> bool {
+        match self {
+            Keyword { category, keyword } => state
+                .iter()
+                .filter(|(cat, _)| category.iter().all(|name| cat.name == *name))
+                .any(|(_, kws)| kws.iter().any(|(kw, tf)| *tf && kw.matches(keyword))),
+            Count { category, cmp, n } => state
+                .iter()
+                .filter(|(cat, _)| cat.name == *category)
+                .any(|(cat, kws)| cmp.apply(filename::written(cat, kws).len(), *n)),
+            Not(q) => !q.matches(state),
+            And(l, r) => l.matches(state) && r.matches(state),
+            Or(l, r) => l.matches(state) || r.matches(state),
+        }
+    }
+}
+
+#[test]
+fn query_check() {
+    let schema = crate::fixtures::media_people();
+    assert!(compile(&schema, "Media:photo AND NOT People:nate").is_ok());
+    assert!(compile(&schema, "ph OR People has >= 2").is_ok());
+    assert_eq!(
+        compile(&schema, "Place:home"),
+        Err(QueryError::UnknownCategory("Place".to_string()))
+    );
+    assert_eq!(
+        compile(&schema, "Media:nate"),
+        Err(QueryError::UnknownKeyword {
+            category: Some("Media".to_string()),
+            keyword: "nate".to_string()
+        })
+    );
+    assert_eq!(
+        compile(&schema, "People has > 1 AND bob"),
+        Err(QueryError::UnknownKeyword {
+            category: None,
+            keyword: "bob".to_string()
+        })
+    );
+}
+
+#[test]
+fn query_filter() {
+    let schema = crate::fixtures::media_people();
+    let files: Vec<PathBuf> = [
+        "ABC123-ph-nate.jpg",
+        "ABC124-ph-al.jpg",
+        "ABC125-v-nate-al.mov",
+        "ABC126-art-_.png",
+        "IMG_0001.jpg",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect();
+
+    let run = |input: &str| -> Vec<String> {
+        let query = compile(&schema, input).unwrap();
+        filter(&schema, &query, files.clone())
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect()
+    };
+
+    assert_eq!(
+        run("Media:photo AND NOT People:nate"),
+        vec!["ABC124-ph-al.jpg"]
+    );
+    assert_eq!(run("People has >= 2"), vec!["ABC125-v-nate-al.mov"]);
+    assert_eq!(run("People has 0"), vec!["ABC126-art-_.png"]);
+    assert_eq!(
+        run("nate OR art"),
+        vec![
+            "ABC123-ph-nate.jpg",
+            "ABC125-v-nate-al.mov",
+            "ABC126-art-_.png"
+        ]
+    );
+    assert_eq!(
+        run("NOT (video OR art)"),
+        vec!["ABC123-ph-nate.jpg", "ABC124-ph-al.jpg"]
+    );
+}
+
+#[test]
+fn query_nested() {
+    let schema = crate::fixtures::with_categories(&[
+        r#"category "Subject" (at_least 1) ['animal'/'an' ['dog'/'d', 'cat'/'c'], 'plant'/'pl']"#,
+    ]);
+    let files: Vec<PathBuf> = ["ABC123-d.jpg", "ABC124-an.jpg", "ABC125-c-pl.jpg"]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    let run = |input: &str| -> Vec<String> {
+        let query = compile(&schema, input).unwrap();
+        filter(&schema, &query, files.clone())
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect()
+    };
+
+    // a keyword matches the keywords nested under it
+    assert_eq!(
+        run("animal"),
+        vec!["ABC123-d.jpg", "ABC124-an.jpg", "ABC125-c-pl.jpg"]
+    );
+    assert_eq!(run("dog"), vec!["ABC123-d.jpg"]);
+    assert_eq!(run("animal AND NOT dog AND NOT cat"), vec!["ABC124-an.jpg"]);
+    assert_eq!(run("Subject has 2"), vec!["ABC125-c-pl.jpg"]);
+}
@@ -0,0 +1,202 @@
+use super::{Comparison, Query, Query::*, QueryError};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, tag_no_case, take_till, take_while1},
+    character::complete::{char, digit1, space0},
+    combinator::{complete, map_res, not, opt, peek, verify},
+    multi::fold_many0,
+    sequence::{delimited, preceded, separated_pair, terminated, tuple},
+    IResult, Parser,
+};
+
+/// parses query expressions such as `Media:photo AND NOT People:nate` or `People has >= 2`
+pub fn parse(input: &str) -> Result<Query, QueryError> {
+    match complete(delimited(space0, or_expr, space0)).parse(input) {
+        Ok(("", query)) => Ok(query),
+        Ok((leftover, _)) => Err(QueryError::UnexpectedInput(leftover.to_string())),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            Err(QueryError::UnexpectedInput(e.input.to_string()))
+        }
+        // call to complete skips this branch
+        Err(nom::Err::Incomplete(_)) => {
+            panic!("reached unreachable nom::Err::Incomplete while parsing a query.")
+        }
+    }
+}
+
+fn or_expr(input: &str) -> IResult<&str, Query> {
+    let (input, first) = and_expr(input)?;
+    fold_many0(
+        preceded(operator("OR"), and_expr),
+        move || first.clone(),
+        |l, r| Or(Box::new(l), Box::new(r)),
+    )(input)
+}
+
+fn and_expr(input: &str) -> IResult<&str, Query> {
+    let (input, first) = not_expr(input)?;
+    fold_many0(
+        preceded(operator("AND"), not_expr),
+        move || first.clone(),
+        |l, r| And(Box::new(l), Box::new(r)),
+    )(input)
+}
+
+fn not_expr(input: &str) -> IResult<&str, Query> {
+    alt((
+        preceded(operator("NOT"), not_expr).map(|q| Not(Box::new(q))),
+        term,
+    ))(input)
+}
+
+fn term(input: &str) -> IResult<&str, Query> {
+    alt((
+        lexeme(delimited(char('('), preceded(space0, or_expr), char(')'))),
+        count,
+        separated_pair(name, lexeme(char(':')), name).map(|(category, keyword)| Keyword {
+            category: Some(category),
+            keyword,
+        }),
+        name.map(|keyword| Keyword {
+            category: None,
+            keyword,
+        }),
+    ))(input)
+}
+
+fn count(input: &str) -> IResult<&str, Query> {
+    tuple((
+        name,
+        operator("has"),
+        lexeme(opt(comparison)),
+        lexeme(map_res(digit1, str::parse)),
+    ))
+    .map(|(category, _, cmp, n)| Count {
+        category,
+        cmp: cmp.unwrap_or(Comparison::Eq),
+        n,
+    })
+    .parse(input)
+}
+
+fn comparison(input: &str) -> IResult<&str, Comparison> {
+    alt((
+        tag(">=").map(|_| Comparison::Ge),
+        tag("<=").map(|_| Comparison::Le),
+        tag(">").map(|_| Comparison::Gt),
+        tag("<").map(|_| Comparison::Lt),
+        tag("=").map(|_| Comparison::Eq),
+    ))(input)
+}
+
+/// category and keyword names are bare words, or quoted like they are in the schema when they contain spaces.
+fn name(input: &str) -> IResult<&str, String> {
+    lexeme(alt((
+        delimited(char('\''), take_till(|c| c == '\''), char('\'')),
+        delimited(char('"'), take_till(|c| c == '"'), char('"')),
+        verify(take_while1(is_word_char), |word: &str| {
+            !["AND", "OR", "NOT"].contains(&word.to_uppercase().as_str())
+        }),
+    )))
+    .map(|s: &str| s.to_string())
+    .parse(input)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+/// case insensitive reserved word that can't run into the following word
+fn operator<'a>(op: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    lexeme(terminated(
+        tag_no_case(op),
+        not(peek(take_while1(is_word_char))),
+    ))
+}
+
+fn lexeme<'a, O, F>(inner: F) -> impl FnMut(&'a str) -> IResult<&'a str, O>
+where
+    F: Parser<&'a str, O, nom::error::Error<&'a str>>,
+{
+    let mut inner = terminated(inner, space0);
+    move |input| inner.parse(input)
+}
+
+#[cfg(test)]
+fn kw(category: Option<&str>, keyword: &str) -> Query {
+    Keyword {
+        category: category.map(|s| s.to_string()),
+        keyword: keyword.to_string(),
+    }
+}
+
+#[test]
+fn parse_terms() {
+    assert_eq!(parse("photo"), Ok(kw(None, "photo")));
+    assert_eq!(parse(" Media:photo "), Ok(kw(Some("Media"), "photo")));
+    assert_eq!(parse("Media : photo"), Ok(kw(Some("Media"), "photo")));
+    assert_eq!(
+        parse(r#""Place Taken":'new york'"#),
+        Ok(kw(Some("Place Taken"), "new york"))
+    );
+    assert_eq!(
+        parse("People has >= 2"),
+        Ok(Count {
+            category: "People".to_string(),
+            cmp: Comparison::Ge,
+            n: 2
+        })
+    );
+    assert_eq!(
+        parse("People has 0"),
+        Ok(Count {
+            category: "People".to_string(),
+            cmp: Comparison::Eq,
+            n: 0
+        })
+    );
+}
+
+#[test]
+fn parse_operators() {
+    assert_eq!(
+        parse("Media:photo AND NOT People:nate"),
+        Ok(And(
+            Box::new(kw(Some("Media"), "photo")),
+            Box::new(Not(Box::new(kw(Some("People"), "nate"))))
+        ))
+    );
+    // AND binds tighter than OR
+    assert_eq!(
+        parse("a or b and c"),
+        Ok(Or(
+            Box::new(kw(None, "a")),
+            Box::new(And(Box::new(kw(None, "b")), Box::new(kw(None, "c"))))
+        ))
+    );
+    assert_eq!(
+        parse("(a OR b) AND c"),
+        Ok(And(
+            Box::new(Or(Box::new(kw(None, "a")), Box::new(kw(None, "b")))),
+            Box::new(kw(None, "c"))
+        ))
+    );
+    assert_eq!(parse("NOT(a)"), Ok(Not(Box::new(kw(None, "a")))));
+    // operators only match whole words
+    assert_eq!(parse("android"), Ok(kw(None, "android")));
+    assert_eq!(parse("NOTE"), Ok(kw(None, "NOTE")));
+}
+
+#[test]
+fn parse_query_errors() {
+    assert_eq!(
+        parse("a AND"),
+        Err(QueryError::UnexpectedInput("AND".to_string()))
+    );
+    assert_eq!(
+        parse("(a"),
+        Err(QueryError::UnexpectedInput("(a".to_string()))
+    );
+    assert!(parse("").is_err());
+    assert!(parse("AND").is_err());
+}
@@ -0,0 +1,429 @@
+use super::{typecheck::SchemaPart, Keyword, Schema, SchemaTypeCheckError, ValueKind};
+use crate::filename::{
+    limit::{Budget, Part, Profile, Unit},
+    ID_LEN,
+};
+use chrono::{
+    format::{Item, StrftimeItems},
+    Days, NaiveDate,
+};
+use std::{collections::HashMap, fmt, path::Path};
+
+/// extension budget used when one isn't given: the dot and four characters like `.jpeg`
+pub const DEFAULT_EXT_LEN: usize = 5;
+
+/// a length measured in every unit file systems limit names by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Length {
+    pub chars: usize,
+    pub bytes: usize,
+    pub utf16: usize,
+}
+
+impl Length {
+    pub fn get(&self, unit: Unit) -> usize {
+        match unit {
+            Unit::Chars => self.chars,
+            Unit::Bytes => self.bytes,
+            Unit::Utf16 => self.utf16,
+        }
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}, {} {}, {} {}",
+            self.chars,
+            Unit::Chars,
+            self.bytes,
+            Unit::Bytes,
+            self.utf16,
+            Unit::Utf16
+        )
+    }
+}
+
+/// the longest name the schema can generate, including the file id and an extension of `ext_len` ascii characters counting the dot.
+pub fn max_name_length(schema: &Schema, ext_len: usize) -> Length {
+    Length {
+        chars: max_name_length_in(schema, ext_len, Unit::Chars),
+        bytes: max_name_length_in(schema, ext_len, Unit::Bytes),
+        utf16: max_name_length_in(schema, ext_len, Unit::Utf16),
+    }
+}
+
+/// checks the longest possible name against the profile's limits, returning each limit it can go over and by how much.
+pub fn exceeds(profile: &Profile, dir: &Path, length: &Length) -> Vec<(Part, Budget, usize)> {
+    let mut over = vec![];
+    if let Some(budget) = profile.name {
+        let len = length.get(budget.unit);
+        if len > budget.max {
+            over.push((Part::Name, budget, len));
+        }
+    }
+    if let Some(budget) = profile.path {
+        // the directory, a separator, then the name
+        let dir_len = budget.unit.len(&dir.to_string_lossy()) + 1;
+        let len = dir_len + length.get(budget.unit);
+        if len > budget.max {
+            over.push((Part::Path, budget, len));
+        }
+    }
+    over
+}
+
+/// names are split on the delimiter and each segment is matched against keyword ids,
+/// so every id has to be distinct, non-empty, and free of the delimiter for that to be unambiguous.
+pub fn check_decodable(schema: &Schema) -> Vec<(SchemaTypeCheckError, SchemaPart)> {
+    use SchemaTypeCheckError::*;
+    let mut errors = vec![];
+    let delim = (!schema.delim.is_empty()).then_some(schema.delim.as_str());
+    if delim.is_none() {
+        errors.push((EmptyDelimiter, SchemaPart::Delimiter));
+    }
+    if schema.delim.contains('.') {
+        errors.push((DotInDelimiter, SchemaPart::Delimiter));
+    }
+    if schema.empty.is_empty()
+        || schema.empty.contains('.')
+        || delim.is_some_and(|d| runs_into(&schema.empty, d))
+    {
+        errors.push((InvalidEmptyMarker(schema.empty.clone()), SchemaPart::Empty));
+    }
+
+    // text can be almost anything, so it has keyword ids taken out as it's entered instead
+    let values: Vec<(&str, &ValueKind)> = schema
+        .categories
+        .iter()
+        .filter_map(|(cat, _)| Some((cat.name.as_str(), cat.value.as_ref()?)))
+        .filter(|(_, kind)| !matches!(kind, ValueKind::Text(_)))
+        .collect();
+    let mut seen: HashMap<&str, (&str, &str)> = HashMap::new();
+    for (i, (cat, kws)) in schema.categories.iter().enumerate() {
+        // text has the delimiter taken out as it's entered, but dates and numbers are written as they are
+        let value_has_delim = match &cat.value {
+            Some(ValueKind::Date(format)) => sample_dates().any(|d| {
+                delim.is_some_and(|delim| runs_into(&d.format(format).to_string(), delim))
+            }),
+            Some(ValueKind::Number(min, max)) => {
+                (*min..=*max).any(|n| delim.is_some_and(|delim| runs_into(&n.to_string(), delim)))
+            }
+            Some(ValueKind::Text(_)) | None => false,
+        };
+        if value_has_delim {
+            errors.push((DelimiterInValue(cat.name.clone()), SchemaPart::Category(i)));
+        }
+        for (j, kw) in kws.iter().enumerate() {
+            let at = SchemaPart::Keyword(i, j);
+            // ids a keyword used to have are still read, so they have to be as distinct as the current one
+            for id in std::iter::once(&kw.id).chain(cat.former_ids(j)) {
+                let (category, keyword) = (cat.name.clone(), kw.name.clone());
+                if id.is_empty() {
+                    errors.push((EmptyId { category, keyword }, at));
+                } else if delim.is_some_and(|d| runs_into(id, d)) {
+                    let id = id.clone();
+                    let e = DelimiterInKeywordId {
+                        category,
+                        keyword,
+                        id,
+                    };
+                    errors.push((e, at));
+                } else if id.contains('.') {
+                    let id = id.clone();
+                    let e = DotInKeywordId {
+                        category,
+                        keyword,
+                        id,
+                    };
+                    errors.push((e, at));
+                } else if *id == schema.empty {
+                    errors.push((IdCollidesWithEmpty { category, keyword }, at));
+                } else if let Some((value, _)) = values.iter().find(|(_, kind)| is_value(kind, id))
+                {
+                    let e = IdCollidesWithValue {
+                        id: id.clone(),
+                        keyword: Box::new((category, keyword)),
+                        value: value.to_string(),
+                    };
+                    errors.push((e, at));
+                } else if let Some((other_cat, other_kw)) = seen.insert(id, (&cat.name, &kw.name)) {
+                    let e = DuplicateKeywordId {
+                        id: id.clone(),
+                        first: Box::new((other_cat.to_string(), other_kw.to_string())),
+                        second: Box::new((category, keyword)),
+                    };
+                    errors.push((e, at));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// whether a date or number category writes one of its values exactly like the segment
+fn is_value(kind: &ValueKind, segment: &str) -> bool {
+    match kind {
+        ValueKind::Date(format) => NaiveDate::parse_from_str(segment, format)
+            .is_ok_and(|d| d.format(format).to_string() == segment),
+        ValueKind::Number(min, max) => segment
+            .parse::<u8>()
+            .is_ok_and(|n| (*min..=*max).contains(&n) && n.to_string() == segment),
+        ValueKind::Text(_) => false,
+    }
+}
+
+/// whether a segment could be split somewhere other than where it was joined. Besides containing the delimiter,
+/// a segment ending in `-` next to the delimiter `--` reads as `-` followed by the delimiter.
+pub(crate) fn runs_into(segment: &str, delim: &str) -> bool {
+    segment.contains(delim)
+        || (1..delim.len())
+            .filter(|&i| delim.is_char_boundary(i))
+            .any(|i| segment.ends_with(&delim[..i]) || segment.starts_with(&delim[i..]))
+}
+
+/// whether a date written in the format can be read back as the same date
+pub fn is_date_format(format: &str) -> bool {
+    !StrftimeItems::new(format).any(|item| item == Item::Error)
+        && sample_dates().all(|d| {
+            NaiveDate::parse_from_str(&d.format(format).to_string(), format).ok() == Some(d)
+        })
+}
+
+/// every day of a leap year, enough to find the longest way a format writes month and weekday names
+fn sample_dates() -> impl Iterator<Item = NaiveDate> {
+    let start = NaiveDate::from_ymd_opt(2000, 1, 1).expect("a valid date");
+    (0..366).filter_map(move |n| start.checked_add_days(Days::new(n)))
+}
+
+/// the longest value a category can have, or the empty marker when it's left out
+fn value_length(schema: &Schema, value: &ValueKind, unit: Unit) -> usize {
+    let longest = match value {
+        ValueKind::Date(format) => sample_dates()
+            .map(|d| unit.len(&d.format(format).to_string()))
+            .max()
+            .unwrap_or(0),
+        ValueKind::Number(_, max) => max.to_string().len(),
+        ValueKind::Text(max) => {
+            let max = *max as usize;
+            match unit {
+                Unit::Chars => max,
+                Unit::Bytes => 4 * max,
+                Unit::Utf16 => 2 * max,
+            }
+        }
+    };
+    longest.max(unit.len(&schema.empty))
+}
+
+fn max_name_length_in(schema: &Schema, ext_len: usize, unit: Unit) -> usize {
+    let delim = unit.len(&schema.delim);
+    let categories: usize = schema
+        .categories
+        .iter()
+        .map(|(cat, kws)| {
+            if let Some(value) = &cat.value {
+                return value_length(schema, value, unit);
+            }
+            let counts = cat.requirement.min()..=cat.requirement.max().unwrap_or(kws.len());
+            counts
+                // requirements that ask for more keywords than exist can never be met
+                .filter(|k| *k <= kws.len())
+                .map(|k| category_length(schema, kws, k, unit))
+                .max()
+                .unwrap_or(0)
+        })
+        .sum();
+    let delims = schema.categories.len().saturating_sub(1) * delim;
+    // ids are ascii so they're the same length in every unit
+    ID_LEN + delim + categories + delims + ext_len
+}
+
+/// the length of the longest `k` keyword ids joined by the delimiter, or the empty marker when `k` is 0
+fn category_length(schema: &Schema, kws: &[Keyword], k: usize, unit: Unit) -> usize {
+    if k == 0 {
+        return unit.len(&schema.empty);
+    }
+    let mut lens: Vec<usize> = kws.iter().map(|kw| unit.len(&kw.id)).collect();
+    lens.sort_unstable_by(|a, b| b.cmp(a));
+    lens.iter().take(k).sum::<usize>() + (k - 1) * unit.len(&schema.delim)
+}
+
+#[cfg(test)]
+use crate::fixtures::schema as schema_from;
+
+#[test]
+fn worst_case_length() {
+    let schema = crate::fixtures::media_people();
+    // ABC123-art-nate-al.jpeg
+    assert_eq!(
+        max_name_length(&schema, DEFAULT_EXT_LEN),
+        Length {
+            chars: 23,
+            bytes: 23,
+            utf16: 23
+        }
+    );
+
+    // only the two longest ids count
+    let schema =
+        schema_from(r#"schema "-" "_" [ category "People" (between 1 2) ['nate', 'alice', 'al']]"#);
+    assert_eq!(max_name_length(&schema, 0).chars, 17);
+
+    // the empty marker can be longer than any keyword
+    let schema = schema_from(
+        r#"schema "-" "nobody" [ category "People" (at_most 1) ['nate', 'alice'/'al']]"#,
+    );
+    assert_eq!(max_name_length(&schema, 0).chars, 13);
+
+    let schema = schema_from(r#"schema "-" "_" [ category "People" (at_least 1) ['子曰', 'a']]"#);
+    // ABC123-子曰-a.jpg
+    assert_eq!(
+        max_name_length(&schema, 4),
+        Length {
+            chars: 15,
+            bytes: 19,
+            utf16: 15
+        }
+    );
+}
+
+#[test]
+fn ambiguous_schemas() {
+    use super::{parse::parse, typecheck::typecheck, SchemaTypeCheckError::*};
+    let check = |input: &str| typecheck(parse(input).unwrap()).map_err(|mut es| es.remove(0).value);
+    let named = |category: &str, keyword: &str| (category.to_string(), keyword.to_string());
+
+    assert_eq!(
+        check(r#"schema "" "_" [ category "Media" (exactly 1) ['art']]"#),
+        Err(EmptyDelimiter)
+    );
+    assert_eq!(
+        check(r#"schema "." "_" [ category "Media" (exactly 1) ['art']]"#),
+        Err(DotInDelimiter)
+    );
+    assert_eq!(
+        check(r#"schema "-" "-_-" [ category "Media" (exactly 1) ['art']]"#),
+        Err(InvalidEmptyMarker("-_-".to_string()))
+    );
+    assert_eq!(
+        check(r#"schema "-" "_" [ category "Media" (exactly 1) ['art', 'photo'/'']]"#),
+        Err(EmptyId {
+            category: "Media".to_string(),
+            keyword: "photo".to_string()
+        })
+    );
+    assert_eq!(
+        check(r#"schema "-" "_" [ category "Media" (exactly 1) ['art', 'photo'/'p-h']]"#),
+        Err(DelimiterInKeywordId {
+            category: "Media".to_string(),
+            keyword: "photo".to_string(),
+            id: "p-h".to_string()
+        })
+    );
+    assert_eq!(
+        check(r#"schema "-" "_" [ category "People" (at_least 0) ['nobody'/'_']]"#),
+        Err(IdCollidesWithEmpty {
+            category: "People".to_string(),
+            keyword: "nobody".to_string()
+        })
+    );
+    assert_eq!(
+        check(
+            r#"schema "-" "_" [ category "Media" (exactly 1) ['photo'/'ph'], category "Devices" (at_most 1) ['phone'/'ph']]"#
+        ),
+        Err(DuplicateKeywordId {
+            id: "ph".to_string(),
+            first: Box::new(named("Media", "photo")),
+            second: Box::new(named("Devices", "phone"))
+        })
+    );
+    // a keyword id that's also a value can't be told apart from it
+    assert_eq!(
+        check(
+            r#"schema "-" "_" [ number "Rating" 1 5, category "Media" (exactly 1) ['art', 'third'/'3']]"#
+        ),
+        Err(IdCollidesWithValue {
+            id: "3".to_string(),
+            keyword: Box::new(named("Media", "third")),
+            value: "Rating".to_string()
+        })
+    );
+    assert_eq!(
+        check(
+            r#"schema "-" "_" [ date "Taken" "%Y%m%d", category "Day" (at_most 1) ['leap'/'20240229']]"#
+        ),
+        Err(IdCollidesWithValue {
+            id: "20240229".to_string(),
+            keyword: Box::new(named("Day", "leap")),
+            value: "Taken".to_string()
+        })
+    );
+    assert!(check(
+        r#"schema "-" "_" [ number "Rating" 1 5, text "Caption" (max_len 10), category "Media" (exactly 1) ['art', 'six'/'6']]"#
+    )
+    .is_ok());
+    // former ids are read from names too
+    assert_eq!(
+        check(
+            r#"schema "-" "_" [ category "Media" (exactly 1) ['photo'/'ph', 'video'/'v' (was 'ph')]]"#
+        ),
+        Err(DuplicateKeywordId {
+            id: "ph".to_string(),
+            first: Box::new(named("Media", "photo")),
+            second: Box::new(named("Media", "video"))
+        })
+    );
+    // names these would make can't be split back into the same segments
+    assert_eq!(
+        check(r#"schema "--" "_" [ category "Media" (exactly 1) ['x'/'x-', 'z']]"#),
+        Err(DelimiterInKeywordId {
+            category: "Media".to_string(),
+            keyword: "x".to_string(),
+            id: "x-".to_string()
+        })
+    );
+    assert_eq!(
+        check(r#"schema "--" "-" [ category "Media" (at_most 1) ['z']]"#),
+        Err(InvalidEmptyMarker("-".to_string()))
+    );
+    assert_eq!(
+        check(r#"schema "-" "_" [ category "Version" (exactly 1) ['v1.0']]"#),
+        Err(DotInKeywordId {
+            category: "Version".to_string(),
+            keyword: "v1.0".to_string(),
+            id: "v1.0".to_string()
+        })
+    );
+    assert_eq!(
+        check(r#"schema "-" "_" [ category "Media" (exactly 1) ['photo'/'ph' (was 'p-h')]]"#),
+        Err(DelimiterInKeywordId {
+            category: "Media".to_string(),
+            keyword: "photo".to_string(),
+            id: "p-h".to_string()
+        })
+    );
+}
+
+#[test]
+fn worst_case_exceeds() {
+    let length = Length {
+        chars: 200,
+        bytes: 300,
+        utf16: 200,
+    };
+    let dir = Path::new("/photos");
+    assert!(exceeds(&Profile::APFS, dir, &length).is_empty());
+    assert_eq!(
+        exceeds(&Profile::EXT4, dir, &length),
+        vec![(Part::Name, Budget::new(Unit::Bytes, 255), 300)]
+    );
+    assert!(exceeds(&Profile::NTFS, dir, &length).is_empty());
+    let deep = Path::new("/").join("a".repeat(99));
+    // 100 for the directory, 1 for the separator, and 200 for the name
+    assert_eq!(
+        exceeds(&Profile::NTFS, &deep, &length),
+        vec![(Part::Path, Budget::new(Unit::Utf16, 260), 301)]
+    );
+}
@@ -0,0 +1,343 @@
+use super::{
+    parse, Diagnostic,
+    ExprU::{self, *},
+    Location, SchemaImportError, Span,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// every file that went into a schema. Spans from imported files are shifted past the end of the
+/// files before them so a span alone is enough to find the file it's in.
+pub struct Sources {
+    files: Vec<Source>,
+}
+
+struct Source {
+    path: PathBuf,
+    /// used to recognize the same file imported through different paths
+    canonical: PathBuf,
+    text: String,
+    /// where the file starts among all of the files
+    base: usize,
+    importer: Option<usize>,
+}
+
+impl Sources {
+    pub fn new(path: &Path, text: &str) -> Self {
+        Sources {
+            files: vec![Source {
+                path: path.to_path_buf(),
+                canonical: fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+                text: text.to_string(),
+                base: 0,
+                importer: None,
+            }],
+        }
+    }
+
+    /// reads and parses the files imported by an expression from `file`, and the files they import.
+    /// Anything that goes wrong is added to the diagnostics and leaves the import unresolved.
+    pub fn resolve(
+        &mut self,
+        expr: ExprU,
+        file: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> ExprU {
+        match expr {
+            FnU { name, args, span } => FnU {
+                name,
+                args: args
+                    .into_iter()
+                    .map(|x| self.resolve(x, file, diagnostics))
+                    .collect(),
+                span,
+            },
+            NestedU {
+                parent,
+                children,
+                span,
+            } => NestedU {
+                parent,
+                children: Box::new(self.resolve(*children, file, diagnostics)),
+                span,
+            },
+            ListU(xs, span) => ListU(
+                xs.into_iter()
+                    .map(|x| self.resolve(x, file, diagnostics))
+                    .collect(),
+                span,
+            ),
+            LetU {
+                name,
+                value,
+                body,
+                span,
+            } => LetU {
+                name,
+                value: Box::new(self.resolve(*value, file, diagnostics)),
+                body: Box::new(self.resolve(*body, file, diagnostics)),
+                span,
+            },
+            ImportU {
+                path, name, span, ..
+            } => {
+                let resolved = match self.import(&path, name.as_deref(), file, diagnostics) {
+                    Ok(resolved) => resolved.map(Box::new),
+                    Err(e) => {
+                        let (_, location) = self.locate(span);
+                        diagnostics.push(self.in_file(file, Diagnostic::Import(e, location)));
+                        None
+                    }
+                };
+                ImportU {
+                    path,
+                    name,
+                    resolved,
+                    span,
+                }
+            }
+            expr => expr,
+        }
+    }
+
+    /// the expression in the file at `path`, which is relative to the file importing it,
+    /// or the value it binds to `name`
+    fn import(
+        &mut self,
+        path: &str,
+        name: Option<&str>,
+        importer: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Option<ExprU>, SchemaImportError> {
+        let dir = self.files[importer].path.parent().unwrap_or(Path::new(""));
+        let path = dir.join(path);
+        let unreadable = |e: std::io::Error| SchemaImportError::Unreadable {
+            path: self.shown(&path),
+            reason: e.to_string(),
+        };
+        let canonical = fs::canonicalize(&path).map_err(unreadable)?;
+        let chain = self.chain(importer);
+        if chain.iter().any(|&i| self.files[i].canonical == canonical) {
+            let mut cycle: Vec<PathBuf> = chain
+                .into_iter()
+                .rev()
+                .map(|i| self.shown(&self.files[i].path))
+                .collect();
+            cycle.push(self.shown(&path));
+            return Err(SchemaImportError::Cycle(cycle));
+        }
+        let text = fs::read_to_string(&path).map_err(unreadable)?;
+
+        let file = self.files.len();
+        let last = &self.files[file - 1];
+        // one past the end so spans at the end of a file don't land in the next one
+        let base = last.base + last.text.len() + 1;
+        let (expr, errors) = parse::parse_imported(&text);
+        self.files.push(Source {
+            path,
+            canonical,
+            text,
+            base,
+            importer: Some(importer),
+        });
+        for e in errors {
+            diagnostics.push(self.in_file(file, Diagnostic::Parse(e)));
+        }
+        let Some(expr) = expr else {
+            return Ok(None);
+        };
+        let expr = expr.map_spans(&|span| Span {
+            start: span.start + base,
+            end: span.end + base,
+        });
+        let expr = match name {
+            Some(name) => binding(expr, name).ok_or_else(|| SchemaImportError::Unbound {
+                path: self.shown(&self.files[file].path),
+                name: name.to_string(),
+            })?,
+            None => expr,
+        };
+        Ok(Some(self.resolve(expr, file, diagnostics)))
+    }
+
+    /// the file a span is in and where in that file it is
+    pub fn locate(&self, span: Span) -> (usize, Location) {
+        let file = self
+            .files
+            .iter()
+            .rposition(|f| f.base <= span.start)
+            .unwrap_or(0);
+        let Source { text, base, .. } = &self.files[file];
+        let span = Span {
+            start: span.start - base,
+            end: span.end.saturating_sub(*base),
+        };
+        (file, Location::of_span(text, span))
+    }
+
+    /// names the file a diagnostic is from, unless it's the schema file itself
+    pub fn in_file(&self, file: usize, diagnostic: Diagnostic) -> Diagnostic {
+        if file == 0 {
+            return diagnostic;
+        }
+        Diagnostic::Imported {
+            chain: self
+                .chain(file)
+                .into_iter()
+                .map(|i| self.shown(&self.files[i].path))
+                .collect(),
+            diagnostic: Box::new(diagnostic),
+        }
+    }
+
+    /// the path relative to the schema file's directory, to keep messages short
+    fn shown(&self, path: &Path) -> PathBuf {
+        let root = self.files[0].path.parent().unwrap_or(Path::new(""));
+        path.strip_prefix(root).unwrap_or(path).to_path_buf()
+    }
+
+    /// the file followed by the files that imported it, back to the schema file
+    fn chain(&self, file: usize) -> Vec<usize> {
+        let mut chain = vec![file];
+        while let Some(importer) = self.files[chain[chain.len() - 1]].importer {
+            chain.push(importer);
+        }
+        chain
+    }
+}
+
+/// the value bound to `name` by the `let`s a file starts with, inside the `let`s before it so it can
+/// still use them. When the name is bound more than once, the last one is what the file itself would see.
+fn binding(expr: ExprU, name: &str) -> Option<ExprU> {
+    let mut lets = vec![];
+    let mut expr = expr;
+    while let LetU {
+        name: bound,
+        value,
+        body,
+        span,
+    } = expr
+    {
+        lets.push((bound, value, span));
+        expr = *body;
+    }
+    let i = lets.iter().rposition(|(bound, _, _)| bound == name)?;
+    lets.truncate(i + 1);
+    let (_, value, _) = lets.pop()?;
+    Some(
+        lets.into_iter()
+            .rev()
+            .fold(*value, |body, (name, value, span)| LetU {
+                name,
+                value,
+                body: Box::new(body),
+                span,
+            }),
+    )
+}
+
+#[test]
+fn imports() {
+    use super::{load, SchemaTypeCheckError};
+    let dir = crate::fs::test_dir();
+    let root = dir.join("photos");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(dir.join("shared")).unwrap();
+    let write = |path: &str, text: &str| fs::write(dir.join(path), text).unwrap();
+    let check = || {
+        let path = root.join("schema.q");
+        load(&fs::read_to_string(&path).unwrap(), &path)
+    };
+
+    write(
+        "shared/people.q",
+        "let family = ['alice'] in\nfamily ++ ['bob']\n",
+    );
+    write(
+        "photos/schema.q",
+        "let family = ['carol'] in\nschema \"-\" \"_\" [ category \"People\" (at_least 0) (import \"../shared/people.q\") ]",
+    );
+    let schema = check().unwrap();
+    let names: Vec<&str> = schema.categories[0]
+        .1
+        .iter()
+        .map(|kw| kw.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["alice", "bob"]);
+
+    // the names another directory's schema binds can be used without the rest of it
+    fs::create_dir_all(dir.join("family")).unwrap();
+    write(
+        "family/schema.q",
+        "let kids = ['dana'] in\nlet people = category \"People\" (at_least 0) (['erin'] ++ kids) in\nschema \"-\" \"_\" [ people ]",
+    );
+    write(
+        "photos/schema.q",
+        "schema \"-\" \"_\" [ category \"Media\" (exactly 1) ['art'], import people from \"../family/schema.q\" ]",
+    );
+    let schema = check().unwrap();
+    assert_eq!(schema.categories[1].0.name, "People");
+    let names: Vec<&str> = schema.categories[1]
+        .1
+        .iter()
+        .map(|kw| kw.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["erin", "dana"]);
+
+    write(
+        "photos/schema.q",
+        "schema \"-\" \"_\" [ import pets from \"../family/schema.q\" ]",
+    );
+    let [Diagnostic::Import(e @ SchemaImportError::Unbound { .. }, _)] = &check().unwrap_err()[..]
+    else {
+        panic!("expected a missing name");
+    };
+    assert_eq!(
+        e.to_string(),
+        "../family/schema.q doesn't define `pets` with `let`"
+    );
+    write(
+        "photos/schema.q",
+        "schema \"-\" \"_\" [ category \"People\" (at_least 0) (import \"../shared/people.q\") ]",
+    );
+
+    // errors are shown in the file they're in, along with how it was imported
+    write("shared/people.q", "['alice', nobody]");
+    let [Diagnostic::Imported { chain, diagnostic }] = &check().unwrap_err()[..] else {
+        panic!("expected an error in the imported file");
+    };
+    assert_eq!(
+        chain,
+        &vec![Path::new("../shared/people.q"), Path::new("schema.q")]
+    );
+    assert!(matches!(
+        **diagnostic,
+        Diagnostic::Typecheck(
+            SchemaTypeCheckError::UnboundVariable(_),
+            Location {
+                line: 1,
+                column: 11,
+                ..
+            }
+        )
+    ));
+
+    write("shared/people.q", "import \"../photos/schema.q\"");
+    let [Diagnostic::Imported { diagnostic, .. }] = &check().unwrap_err()[..] else {
+        panic!("expected an import cycle");
+    };
+    assert_eq!(
+        diagnostic.to_string().lines().next(),
+        Some("Import cycle: schema.q → ../shared/people.q → ../shared/../photos/schema.q")
+    );
+
+    fs::remove_file(dir.join("shared/people.q")).unwrap();
+    assert!(matches!(
+        &check().unwrap_err()[..],
+        [Diagnostic::Import(SchemaImportError::Unreadable { .. }, _)]
+    ));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -1,7 +1,13 @@
+pub mod analysis;
+pub mod import;
 pub mod parse;
 pub mod typecheck;
 
-use std::{error::Error as StdError, fmt};
+use std::{
+    error::Error as StdError,
+    fmt,
+    path::{Path, PathBuf},
+};
 use typecheck::Type;
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -9,12 +15,97 @@ pub struct Schema {
     pub delim: String,
     pub empty: String,
     pub categories: Vec<(Category, Vec<Keyword>)>,
+    /// rules about which keywords can be selected together
+    pub constraints: Vec<Constraint>,
+}
+
+impl Schema {
+    pub fn keyword(&self, at: KeywordRef) -> &Keyword {
+        &self.categories[at.category].1[at.keyword]
+    }
+
+    /// the keywords that all have to be selected for the category to apply
+    pub fn conditions(&self, category: usize) -> Vec<KeywordRef> {
+        self.constraints
+            .iter()
+            .filter_map(|c| match *c {
+                Constraint::When(at, cat) if cat == category => Some(at),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// a keyword by where it is in the schema
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct KeywordRef {
+    pub category: usize,
+    pub keyword: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Constraint {
+    /// the first keyword can only be selected along with the second
+    Requires(KeywordRef, KeywordRef),
+    /// the keywords can't both be selected
+    Excludes(KeywordRef, KeywordRef),
+    /// the category only applies when the keyword is selected. Otherwise nothing in it can be.
+    When(KeywordRef, usize),
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Category {
     pub name: String,
     pub requirement: Requirement,
+    /// what's entered for categories that take a value instead of keywords
+    pub value: Option<ValueKind>,
+    /// the index of the keyword each keyword is nested under, if any
+    pub parents: Vec<Option<usize>>,
+    /// ids each keyword used to have, which are still read from names
+    pub former_ids: Vec<Vec<String>>,
+}
+
+impl Category {
+    pub fn former_ids(&self, keyword: usize) -> &[String] {
+        self.former_ids.get(keyword).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn parent(&self, keyword: usize) -> Option<usize> {
+        self.parents.get(keyword).copied().flatten()
+    }
+
+    /// whether the keyword is nested under the other one, at any depth
+    pub fn is_under(&self, keyword: usize, ancestor: usize) -> bool {
+        let mut at = self.parent(keyword);
+        while let Some(parent) = at {
+            if parent == ancestor {
+                return true;
+            }
+            at = self.parent(parent);
+        }
+        false
+    }
+}
+
+/// values are stored in the state as a single selected keyword, with the value in the filename as its id
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ValueKind {
+    /// written with a strftime style format like `%Y%m%d`
+    Date(String),
+    /// a whole number in an inclusive range
+    Number(u8, u8),
+    /// text of at most this many characters
+    Text(u8),
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Date(format) => write!(f, "a date formatted as {format}"),
+            Self::Number(min, max) => write!(f, "a number from {min} to {max}"),
+            Self::Text(max) => write!(f, "text of up to {max} characters"),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -22,6 +113,30 @@ pub enum Requirement {
     Exactly(u8),
     AtLeast(u8),
     AtMost(u8),
+    /// inclusive on both ends
+    Between(u8, u8),
+}
+
+impl Requirement {
+    /// the fewest keywords that can be selected
+    pub fn min(&self) -> usize {
+        match *self {
+            Self::Exactly(n) | Self::AtLeast(n) | Self::Between(n, _) => n as usize,
+            Self::AtMost(_) => 0,
+        }
+    }
+
+    /// the most keywords that can be selected, if there's a limit
+    pub fn max(&self) -> Option<usize> {
+        match *self {
+            Self::Exactly(n) | Self::AtMost(n) | Self::Between(_, n) => Some(n as usize),
+            Self::AtLeast(_) => None,
+        }
+    }
+
+    pub fn allows(&self, count: usize) -> bool {
+        self.min() <= count && self.max().into_iter().all(|max| count <= max)
+    }
 }
 
 impl fmt::Display for Requirement {
@@ -30,6 +145,7 @@ impl fmt::Display for Requirement {
             Self::Exactly(n) => write!(f, "exactly {n}"),
             Self::AtLeast(n) => write!(f, "at least {n}"),
             Self::AtMost(n) => write!(f, "at most {n}"),
+            Self::Between(min, max) => write!(f, "between {min} and {max}"),
         }
     }
 }
@@ -40,10 +156,80 @@ pub struct Keyword {
     pub id: String,
 }
 
+impl Keyword {
+    /// keywords can be referred to by either their name or their id
+    pub fn matches(&self, s: &str) -> bool {
+        self.name == s || self.id == s
+    }
+}
+
+/// a position in the schema source along with the line it's on so errors can point at it
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Location {
+    /// 1-based
+    pub line: u32,
+    /// 1-based, counted in characters
+    pub column: usize,
+    /// characters to underline, stopping at the end of the line
+    pub width: usize,
+    pub source_line: String,
+}
+
+impl Location {
+    /// the location of a byte offset into the source
+    pub fn new(source: &str, offset: usize) -> Self {
+        Self::of_span(
+            source,
+            Span {
+                start: offset,
+                end: offset,
+            },
+        )
+    }
+
+    /// the location of the start of a span, underlining as much of it as is on that line
+    pub fn of_span(source: &str, span: Span) -> Self {
+        let offset = span.start.min(source.len());
+        let start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |i| offset + i);
+        let width = source[offset..span.end.clamp(offset, end)].chars().count();
+        Location {
+            line: source[..offset].matches('\n').count() as u32 + 1,
+            column: source[start..offset].chars().count() + 1,
+            width: width.max(1),
+            source_line: source[start..end].to_string(),
+        }
+    }
+}
+
+/// renders the line with carets under the location like rustc does
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        // keep tabs so the caret lines up with the text above it
+        let padding: String = self
+            .source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        writeln!(f, "{gutter}--> {}:{}", self.line, self.column)?;
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        write!(f, "{gutter} | {padding}{}", "^".repeat(self.width))
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum SchemaParseError {
     MustStartWithSchemaConstructor,
-    UnexpectedInput(String),
+    UnexpectedInput {
+        location: Location,
+        /// descriptions of what would have been accepted here
+        expected: Vec<String>,
+    },
 }
 
 impl fmt::Display for SchemaParseError {
@@ -52,7 +238,14 @@ impl fmt::Display for SchemaParseError {
             Self::MustStartWithSchemaConstructor => {
                 write!(f, "Expected \"schema\" constructor")
             }
-            Self::UnexpectedInput(input) => write!(f, "Unexpected input: {input}"),
+            Self::UnexpectedInput { location, expected } => {
+                match &expected[..] {
+                    [] => writeln!(f, "Unexpected input")?,
+                    [x] => writeln!(f, "Expected {x}")?,
+                    [xs @ .., x] => writeln!(f, "Expected one of {} or {x}", xs.join(", "))?,
+                }
+                write!(f, "{location}")
+            }
         }
     }
 }
@@ -62,9 +255,68 @@ impl StdError for SchemaParseError {}
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum SchemaTypeCheckError {
     HeterogeneousList(Vec<Type>),
-    TypeMismatch { expected: Type, got: Type },
-    UnknownFunction { name: String, arg_types: Vec<Type> },
+    TypeMismatch {
+        expected: Type,
+        got: Type,
+    },
+    UnknownFunction {
+        name: String,
+        arg_types: Vec<Type>,
+    },
     ExpectedTopLevelSchema,
+    /// a keyword in a constraint that isn't in any category
+    UnknownKeyword(String),
+    /// a keyword in a constraint that's the name of keywords in more than one category
+    AmbiguousKeyword(String),
+    ConditionInOwnCategory(String),
+    /// a date format that can't be read back into the date it was written from
+    InvalidDateFormat(String),
+    /// a category whose values can contain the delimiter
+    DelimiterInValue(String),
+    /// `between` with a minimum over its maximum
+    EmptyRange {
+        min: u8,
+        max: u8,
+    },
+    /// a name that isn't bound by an enclosing `let`
+    UnboundVariable(String),
+    EmptyDelimiter,
+    /// names are split on the last `.` for the extension, so it can't be part of the delimiter
+    DotInDelimiter,
+    /// the empty marker is blank, runs into the delimiter, or has a `.`
+    InvalidEmptyMarker(String),
+    EmptyId {
+        category: String,
+        keyword: String,
+    },
+    DelimiterInKeywordId {
+        category: String,
+        keyword: String,
+        id: String,
+    },
+    /// a `.` in an id at the end of a name without an extension would be read as the start of one
+    DotInKeywordId {
+        category: String,
+        keyword: String,
+        id: String,
+    },
+    IdCollidesWithEmpty {
+        category: String,
+        keyword: String,
+    },
+    /// a date or number category could have a value written the same way as the id.
+    /// `keyword` is the category and keyword names, and `value` is the category with the value.
+    IdCollidesWithValue {
+        id: String,
+        keyword: Box<(String, String)>,
+        value: String,
+    },
+    /// the category and keyword names of both keywords
+    DuplicateKeywordId {
+        id: String,
+        first: Box<(String, String)>,
+        second: Box<(String, String)>,
+    },
 }
 
 impl fmt::Display for SchemaTypeCheckError {
@@ -86,19 +338,312 @@ impl fmt::Display for SchemaTypeCheckError {
                 display_types(arg_types)
             ),
             Self::ExpectedTopLevelSchema => write!(f, "The top level value must be a schema."),
+            Self::UnknownKeyword(name) => write!(
+                f,
+                "No keyword in the schema is named \"{name}\" or has it as an id."
+            ),
+            Self::AmbiguousKeyword(name) => write!(
+                f,
+                "More than one keyword is named \"{name}\". Refer to it by its id instead."
+            ),
+            Self::ConditionInOwnCategory(category) => write!(
+                f,
+                "Category {category} can't depend on one of its own keywords."
+            ),
+            Self::InvalidDateFormat(format) => write!(
+                f,
+                "The date format \"{format}\" has to include the year, month, and day."
+            ),
+            Self::DelimiterInValue(category) => write!(
+                f,
+                "Values of category {category} can contain the delimiter."
+            ),
+            Self::EmptyRange { min, max } => write!(
+                f,
+                "between {min} and {max} can never be met. The minimum has to be at most the maximum."
+            ),
+            Self::UnboundVariable(name) => write!(f, "\"{name}\" isn't defined by a `let`."),
+            Self::EmptyDelimiter => write!(f, "The delimiter can't be empty."),
+            Self::DotInDelimiter => write!(
+                f,
+                "The delimiter can't contain a `.` since it would be mistaken for the extension."
+            ),
+            Self::InvalidEmptyMarker(empty) => write!(
+                f,
+                "The empty marker \"{empty}\" must be non-empty, and can't contain a `.` or the delimiter, or start or end with part of it."
+            ),
+            Self::EmptyId { category, keyword } => {
+                write!(f, "Keyword {keyword} in {category} has an empty id.")
+            }
+            Self::DelimiterInKeywordId {
+                category,
+                keyword,
+                id,
+            } => write!(
+                f,
+                "The id \"{id}\" of keyword {keyword} in {category} contains the delimiter, or starts or ends with part of it."
+            ),
+            Self::DotInKeywordId {
+                category,
+                keyword,
+                id,
+            } => write!(
+                f,
+                "The id \"{id}\" of keyword {keyword} in {category} can't contain a `.` since it could be read as the extension."
+            ),
+            Self::IdCollidesWithEmpty { category, keyword } => write!(
+                f,
+                "Keyword {keyword} in {category} has the same id as the empty marker."
+            ),
+            Self::IdCollidesWithValue { id, keyword, value } => write!(
+                f,
+                "The id \"{id}\" of keyword {} in {} could also be a value of {value}, so it couldn't be told which one a name has.",
+                keyword.1, keyword.0
+            ),
+            Self::DuplicateKeywordId { id, first, second } => write!(
+                f,
+                "Keyword id \"{id}\" is used by both {} in {} and {} in {}.",
+                first.1, first.0, second.1, second.0
+            ),
         }
     }
 }
 
 impl StdError for SchemaTypeCheckError {}
 
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum SchemaImportError {
+    /// the files in the order they import each other, ending with the one imported again
+    Cycle(Vec<PathBuf>),
+    Unreadable {
+        path: PathBuf,
+        reason: String,
+    },
+    /// the file doesn't bind the name that was imported from it with `let`
+    Unbound {
+        path: PathBuf,
+        name: String,
+    },
+}
+
+impl fmt::Display for SchemaImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle(chain) => {
+                let chain: Vec<_> = chain.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "Import cycle: {}", chain.join(" → "))
+            }
+            Self::Unreadable { path, reason } => {
+                write!(f, "Couldn't import {}: {reason}", path.display())
+            }
+            Self::Unbound { path, name } => {
+                write!(f, "{} doesn't define `{name}` with `let`", path.display())
+            }
+        }
+    }
+}
+
+impl StdError for SchemaImportError {}
+
+/// a problem with a schema, with enough of the source to show where it is
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Diagnostic {
+    Parse(SchemaParseError),
+    Typecheck(SchemaTypeCheckError, Location),
+    Import(SchemaImportError, Location),
+    /// a problem in an imported file, along with the file and the ones that imported it
+    Imported {
+        chain: Vec<PathBuf>,
+        diagnostic: Box<Diagnostic>,
+    },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "{e}"),
+            Self::Typecheck(e, location) => write!(f, "{e}\n{location}"),
+            Self::Import(e, location) => write!(f, "{e}\n{location}"),
+            Self::Imported { chain, diagnostic } => {
+                write!(f, "{diagnostic}\n  = in {}", chain[0].display())?;
+                for importer in &chain[1..] {
+                    write!(f, ", imported from {}", importer.display())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// parses and typechecks a schema and the files it imports, reporting every error that can be found rather than just the first.
+/// `path` is the file the source was read from, which imports are relative to.
+pub fn load(source: &str, path: &Path) -> Result<Schema, Vec<Diagnostic>> {
+    let mut sources = import::Sources::new(path, source);
+    let (expr, errors) = parse::parse_recovering(source);
+    let mut diagnostics: Vec<Diagnostic> = errors.into_iter().map(Diagnostic::Parse).collect();
+    let expr = expr.map(|expr| sources.resolve(expr, 0, &mut diagnostics));
+    // a partial expression still gets typechecked so errors in the parts that did parse are found too
+    let schema = expr.map(typecheck::typecheck);
+    if let Some(Err(errors)) = &schema {
+        diagnostics.extend(errors.iter().map(|e| {
+            let (file, location) = sources.locate(e.span);
+            sources.in_file(file, Diagnostic::Typecheck(e.value.clone(), location))
+        }));
+    }
+    match schema {
+        Some(Ok(schema)) if diagnostics.is_empty() => Ok(schema),
+        _ => Err(diagnostics),
+    }
+}
+
+/// byte offsets into the schema source
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// a value along with the part of the schema source it came from
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+impl SchemaTypeCheckError {
+    pub fn at(self, span: Span) -> Spanned<Self> {
+        Spanned { value: self, span }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ExprU {
-    KeywordU { name: String, id: String },
-    StringU(String),
-    FnU { name: String, args: Vec<ExprU> },
-    ListU(Vec<ExprU>),
-    NatU(u8),
+    KeywordU {
+        name: String,
+        id: String,
+        span: Span,
+    },
+    /// `'keyword' (was 'old', ...)`, a keyword along with the ids it used to have
+    WasU {
+        keyword: Box<ExprU>,
+        ids: Vec<String>,
+        span: Span,
+    },
+    /// `'parent' [children]`, a keyword with keywords nested under it
+    NestedU {
+        parent: Box<ExprU>,
+        children: Box<ExprU>,
+        span: Span,
+    },
+    StringU(String, Span),
+    FnU {
+        name: String,
+        args: Vec<ExprU>,
+        span: Span,
+    },
+    ListU(Vec<ExprU>, Span),
+    NatU(u8, Span),
+    /// a name bound by `let`
+    VarU(String, Span),
+    /// `import "path"`, which has the value of the file at the path once it's been read.
+    /// `import name from "path"` has the value the file binds to the name with `let` instead.
+    ImportU {
+        path: String,
+        name: Option<String>,
+        resolved: Option<Box<ExprU>>,
+        span: Span,
+    },
+    /// `let name = value in body`, where the name can only be used in the body
+    LetU {
+        name: String,
+        value: Box<ExprU>,
+        body: Box<ExprU>,
+        span: Span,
+    },
+    /// part of the source that didn't parse. The parser has already reported it.
+    ErrorU(Span),
+}
+
+impl ExprU {
+    pub fn span(&self) -> Span {
+        match self {
+            ExprU::KeywordU { span, .. }
+            | ExprU::WasU { span, .. }
+            | ExprU::NestedU { span, .. }
+            | ExprU::StringU(_, span)
+            | ExprU::FnU { span, .. }
+            | ExprU::ListU(_, span)
+            | ExprU::NatU(_, span)
+            | ExprU::VarU(_, span)
+            | ExprU::LetU { span, .. }
+            | ExprU::ImportU { span, .. }
+            | ExprU::ErrorU(span) => *span,
+        }
+    }
+
+    /// the same expression with `f` applied to every span
+    pub fn map_spans(self, f: &impl Fn(Span) -> Span) -> Self {
+        use ExprU::*;
+        match self {
+            KeywordU { name, id, span } => KeywordU {
+                name,
+                id,
+                span: f(span),
+            },
+            WasU { keyword, ids, span } => WasU {
+                keyword: Box::new(keyword.map_spans(f)),
+                ids,
+                span: f(span),
+            },
+            NestedU {
+                parent,
+                children,
+                span,
+            } => NestedU {
+                parent: Box::new(parent.map_spans(f)),
+                children: Box::new(children.map_spans(f)),
+                span: f(span),
+            },
+            StringU(x, span) => StringU(x, f(span)),
+            FnU { name, args, span } => FnU {
+                name,
+                args: args.into_iter().map(|x| x.map_spans(f)).collect(),
+                span: f(span),
+            },
+            ListU(xs, span) => ListU(xs.into_iter().map(|x| x.map_spans(f)).collect(), f(span)),
+            NatU(x, span) => NatU(x, f(span)),
+            VarU(x, span) => VarU(x, f(span)),
+            LetU {
+                name,
+                value,
+                body,
+                span,
+            } => LetU {
+                name,
+                value: Box::new(value.map_spans(f)),
+                body: Box::new(body.map_spans(f)),
+                span: f(span),
+            },
+            ImportU {
+                path,
+                name,
+                resolved,
+                span,
+            } => ImportU {
+                path,
+                name,
+                resolved: resolved.map(|x| Box::new(x.map_spans(f))),
+                span: f(span),
+            },
+            ErrorU(span) => ErrorU(f(span)),
+        }
+    }
+
+    /// the same expression with every span zeroed so parser output can be compared structurally
+    #[cfg(test)]
+    pub fn without_spans(self) -> Self {
+        self.map_spans(&|_| Span::default())
+    }
 }
 
 fn display_types(types: &[Type]) -> String {
@@ -110,3 +655,26 @@ fn display_types(types: &[Type]) -> String {
     x.pop();
     x
 }
+
+#[test]
+fn load_reports_every_error() {
+    let input = "schema \"-\" \"_\"\n  [ category \"Media\" (exactly x) ['art']\n  , category \"People\" (at_leastt 0) ['nate']\n  , category \"Place\" (at_most 1) ['home', \"work\"]\n  ]";
+    let diagnostics = load(input, Path::new("schema.q")).unwrap_err();
+    let lines: Vec<u32> = diagnostics
+        .iter()
+        .map(|d| match d {
+            Diagnostic::Parse(SchemaParseError::UnexpectedInput { location, .. })
+            | Diagnostic::Typecheck(_, location) => location.line,
+            d => panic!("unexpected diagnostic {d:?}"),
+        })
+        .collect();
+    assert_eq!(lines, vec![2, 3, 4]);
+    assert!(matches!(
+        diagnostics[1],
+        Diagnostic::Typecheck(SchemaTypeCheckError::UnknownFunction { .. }, _)
+    ));
+    assert!(matches!(
+        diagnostics[2],
+        Diagnostic::Typecheck(SchemaTypeCheckError::HeterogeneousList(_), _)
+    ));
+}
@@ -1,34 +1,93 @@
-use super::{ExprU, ExprU::*, SchemaParseError};
+use super::{ExprU, ExprU::*, Location, SchemaParseError, Span};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_till},
-    character::complete::{alpha1, char, newline, space0, space1, u8},
-    combinator::{complete, eof, recognize, success},
+    character::complete::{alpha1, char, newline, one_of, u8},
+    combinator::{complete, eof, opt, recognize, value, verify},
     error::{ErrorKind, ParseError},
     multi::{many0, many0_count, many1},
-    sequence::{delimited, pair, preceded, separated_pair, terminated},
-    Err, IResult, InputLength, Parser,
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    Err, IResult, InputLength, InputTake, Parser,
 };
-use std::result::Result as StdResult;
+use nom_locate::LocatedSpan;
+use std::{cell::RefCell, result::Result as StdResult};
 
-pub type Result<T> = StdResult<T, SchemaParseError>;
+pub type Result<T> = StdResult<T, Vec<SchemaParseError>>;
 
-type NomParseResult<'a, O> = StdResult<(&'a str, O), nom::Err<NomParseError<&'a str>>>;
+/// input that keeps track of where it is in the schema source, and where to put errors that were recovered from
+pub type Input<'a> = LocatedSpan<&'a str, &'a RefCell<Vec<Recovered>>>;
+
+/// an error in a list element that parsing skipped past
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recovered {
+    offset: usize,
+    expected: Vec<&'static str>,
+}
+
+type NomParseResult<'a, O> = StdResult<(Input<'a>, O), nom::Err<NomParseError<Input<'a>>>>;
 
 #[derive(Debug, PartialEq)]
 pub enum NomParseError<I> {
     Custom(SchemaParseError),
     Nom(I, ErrorKind),
+    /// what would have been accepted where parsing stopped
+    Expected(I, Vec<&'static str>),
+}
+
+impl<'a> NomParseError<Input<'a>> {
+    fn recovered(&self) -> Recovered {
+        match self {
+            NomParseError::Expected(input, expected) => Recovered {
+                offset: input.location_offset(),
+                expected: expected.clone(),
+            },
+            e => Recovered {
+                offset: e.offset(),
+                expected: vec![],
+            },
+        }
+    }
+
+    fn offset(&self) -> usize {
+        match self {
+            // custom errors aren't tied to a position so they always win
+            NomParseError::Custom(_) => usize::MAX,
+            NomParseError::Nom(input, _) | NomParseError::Expected(input, _) => {
+                input.location_offset()
+            }
+        }
+    }
 }
 
-impl<I> ParseError<I> for NomParseError<I> {
-    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+impl<'a> ParseError<Input<'a>> for NomParseError<Input<'a>> {
+    fn from_error_kind(input: Input<'a>, kind: ErrorKind) -> Self {
         NomParseError::Nom(input, kind)
     }
 
-    fn append(_: I, _: ErrorKind, other: Self) -> Self {
+    fn append(_: Input<'a>, _: ErrorKind, other: Self) -> Self {
         other
     }
+
+    /// keeps the error that got the furthest, merging what was expected when they stopped at the same place
+    fn or(self, other: Self) -> Self {
+        use std::cmp::Ordering::*;
+        match self.offset().cmp(&other.offset()) {
+            Greater => self,
+            Less => other,
+            Equal => match (self, other) {
+                (NomParseError::Expected(input, mut xs), NomParseError::Expected(_, ys)) => {
+                    for y in ys {
+                        if !xs.contains(&y) {
+                            xs.push(y);
+                        }
+                    }
+                    NomParseError::Expected(input, xs)
+                }
+                (e @ NomParseError::Expected(..), _) => e,
+                (_, other) => other,
+            },
+        }
+    }
 }
 
 impl<I> From<SchemaParseError> for NomParseError<I> {
@@ -57,42 +116,195 @@ impl<I> From<nom::Err<(I, ErrorKind)>> for NomParseError<I> {
 }
 
 pub fn parse(input: &str) -> Result<ExprU> {
-    match complete(expr).parse(input) {
+    match parse_recovering(input) {
+        (Some(expr), errors) if errors.is_empty() => Ok(expr),
+        (_, errors) => Err(errors),
+    }
+}
+
+/// parses as much of the schema as possible. List elements that don't parse are replaced
+/// with `ErrorU` so the rest of the schema can still be checked, and every error is returned.
+pub fn parse_recovering(input: &str) -> (Option<ExprU>, Vec<SchemaParseError>) {
+    match parse_imported(input) {
+        (Some(expr), mut errors) if !is_schema(&expr) => {
+            errors.push(SchemaParseError::MustStartWithSchemaConstructor);
+            (None, errors)
+        }
+        result => result,
+    }
+}
+
+/// parses a file that's been imported, which can hold any value rather than only a schema
+pub fn parse_imported(input: &str) -> (Option<ExprU>, Vec<SchemaParseError>) {
+    let recovered = RefCell::new(vec![]);
+    // the file can start and end with comments
+    let result = complete(delimited(line_space0, expr, line_space0))
+        .parse(Input::new_extra(input, &recovered));
+    let mut errors: Vec<SchemaParseError> = recovered
+        .take()
+        .into_iter()
+        .map(|r| unexpected(input, r.offset, r.expected))
+        .collect();
+    let expr = match result {
         Ok((leftover, _)) if !leftover.is_empty() => {
-            Err(SchemaParseError::UnexpectedInput(leftover.to_string()))
+            errors.push(unexpected(
+                input,
+                leftover.location_offset(),
+                vec!["end of input"],
+            ));
+            None
         }
-        Ok((_, schema @ FnU { .. })) => Ok(schema),
-        Ok((_, _)) => Err(SchemaParseError::MustStartWithSchemaConstructor),
+        Ok((_, expr)) => Some(expr),
         Err(e) => match e {
-            nom::Err::Error(e) | nom::Err::Failure(e) => match e {
-                NomParseError::Custom(e) => Err(e),
-                NomParseError::Nom(input, _kind) => {
-                    Err(SchemaParseError::UnexpectedInput(input.to_string()))
-                }
-            },
+            nom::Err::Error(e) | nom::Err::Failure(e) => {
+                errors.push(match e {
+                    NomParseError::Custom(e) => e,
+                    e => {
+                        let r = e.recovered();
+                        unexpected(input, r.offset, r.expected)
+                    }
+                });
+                None
+            }
             // call to complete skips this branch
             nom::Err::Incomplete(_) => {
                 panic!("reached unreachable nom::Err::Incomplete while parsing.")
             }
         },
+    };
+    (expr, errors)
+}
+
+/// whether the expression could be a schema: a function, possibly inside some `let`s.
+/// The typechecker makes sure it's the right function.
+fn is_schema(expr: &ExprU) -> bool {
+    match expr {
+        FnU { .. } | ImportU { .. } => true,
+        LetU { body, .. } => is_schema(body),
+        _ => false,
+    }
+}
+
+fn unexpected(input: &str, offset: usize, expected: Vec<&str>) -> SchemaParseError {
+    SchemaParseError::UnexpectedInput {
+        location: Location::new(input, offset),
+        expected: expected.into_iter().map(str::to_string).collect(),
     }
 }
 
-fn expr(input: &str) -> NomParseResult<ExprU> {
+/// terms joined by `++`
+fn expr(input: Input<'_>) -> NomParseResult<'_, ExprU> {
+    let (mut input, mut acc) = term(input)?;
+    loop {
+        let rest = match delimited(line_space0, tag("++"), line_space0).parse(input) {
+            Ok((rest, _)) => rest,
+            Err(Err::Error(_)) => return Ok((input, acc)),
+            Err(e) => return Err(e),
+        };
+        // anything after a `++` has to be a term
+        let (rest, rhs) = term(rest).map_err(|e| match e {
+            Err::Error(e) => Err::Failure(e),
+            e => e,
+        })?;
+        let span = Span {
+            start: acc.span().start,
+            end: rhs.span().end,
+        };
+        acc = FnU {
+            name: "++".to_string(),
+            args: vec![acc, rhs],
+            span,
+        };
+        input = rest;
+    }
+}
+
+fn term(input: Input<'_>) -> NomParseResult<'_, ExprU> {
     alt((
-        parens(expr),
-        list,
-        func,
-        nat.map(NatU),
+        expecting("`let`", let_),
+        expecting("`import`", import),
+        expecting("`(`", parens(committed(expr))),
+        expecting("a list", list),
+        expecting("a function", func),
+        expecting(
+            "a name",
+            spanned(identifier).map(|(x, span)| VarU(x.to_string(), span)),
+        ),
+        expecting("a number", spanned(nat).map(|(x, span)| NatU(x, span))),
         // keywords are above commands because the syntax leads with a string
-        keyword,
-        string.map(StringU),
+        expecting("a keyword", nested(was(keyword))),
+        expecting(
+            "a string",
+            spanned(string).map(|(x, span)| StringU(x, span)),
+        ),
     ))(input)
 }
 
-fn func(input: &str) -> NomParseResult<ExprU> {
-    let (input, name) = lexeme_vert_allowed(identifier).parse(input)?;
-    let (input, args) = sep_by0(line_space1, expr).parse(input)?;
+/// `let name = value in body`
+fn let_(input: Input<'_>) -> NomParseResult<'_, ExprU> {
+    let start = input.location_offset();
+    let (input, _) = pair(tag("let"), line_space1).parse(input)?;
+    let (input, (name, value, body)) = committed(tuple((
+        terminated(
+            expecting("a name", identifier),
+            delimited(line_space0, expecting("`=`", char('=')), line_space0),
+        ),
+        terminated(
+            committed(expr),
+            pair(
+                line_space0,
+                expecting("`in`", terminated(tag("in"), line_space1)),
+            ),
+        ),
+        committed(expr),
+    )))
+    .parse(input)?;
+    let end = body.span().end;
+    Ok((
+        input,
+        LetU {
+            name: name.to_string(),
+            value: Box::new(value),
+            body: Box::new(body),
+            span: Span { start, end },
+        },
+    ))
+}
+
+/// `import "path"` or `import name from "path"`
+fn import(input: Input<'_>) -> NomParseResult<'_, ExprU> {
+    let start = input.location_offset();
+    let (input, _) = pair(tag("import"), line_space1).parse(input)?;
+    let (input, name) = opt(terminated(
+        identifier,
+        tuple((line_space1, tag("from"), line_space1)),
+    ))
+    .parse(input)?;
+    let (input, (path, span)) = expecting("a path in quotes", spanned(string)).parse(input)?;
+    Ok((
+        input,
+        ImportU {
+            path,
+            name: name.map(str::to_string),
+            resolved: None,
+            span: Span {
+                start,
+                end: span.end,
+            },
+        },
+    ))
+}
+
+fn func(input: Input<'_>) -> NomParseResult<'_, ExprU> {
+    let start = input.location_offset();
+    // a bare word that isn't followed by whitespace wasn't meant to be a function
+    let (input, name) = lexeme_vert_allowed(identifier)
+        .parse(input)
+        .map_err(|_| Err::Error(NomParseError::Nom(input, ErrorKind::Alpha)))?;
+    // a name without arguments is a variable
+    let (input, args) = sep_by1(line_space1, committed(expr)).parse(input)?;
+    // the span covers the name and arguments but not the whitespace around them
+    let end = args[args.len() - 1].span().end;
     // eat any trailing whitespace
     let (input, _) = line_space0.parse(input)?;
     Ok((
@@ -100,99 +312,331 @@ fn func(input: &str) -> NomParseResult<ExprU> {
         FnU {
             name: name.to_string(),
             args,
+            span: Span { start, end },
         },
     ))
 }
 
-fn list(input: &str) -> NomParseResult<ExprU> {
-    let (input, args) = between(
-        '[',
-        ']',
-        alt((
-            sep_by1(
-                delimited(line_space0, tag(","), line_space0),
-                delimited(line_space0, expr, line_space0),
-            ),
-            line_space0.map(|_| vec![]),
-        )),
-    )
+fn list(input: Input<'_>) -> NomParseResult<'_, ExprU> {
+    let (input, (args, span)) = spanned(terminated(
+        preceded(
+            char('['),
+            alt((
+                sep_by1(
+                    delimited(line_space0, tag(","), line_space0),
+                    recovering(delimited(line_space0, committed(expr), line_space0)),
+                ),
+                line_space0.map(|_| vec![]),
+            )),
+        ),
+        expecting_one_of(&["`,`", "`]`"], char(']')),
+    ))
     .parse(input)?;
-    Ok((input, ListU(args)))
+    Ok((input, ListU(args, span)))
 }
 
-fn identifier(input: &str) -> NomParseResult<&str> {
-    recognize(pair(alpha1, many0_count(alt((alpha1, tag("_"))))))(input)
+/// words that can't be used as names
+const RESERVED: [&str; 3] = ["let", "in", "import"];
+
+fn identifier(input: Input<'_>) -> NomParseResult<'_, &str> {
+    verify(
+        recognize(pair(alpha1, many0_count(alt((alpha1, tag("_")))))),
+        |s: &Input<'_>| !RESERVED.contains(s.fragment()),
+    )
+    .map(|s: Input<'_>| *s.fragment())
+    .parse(input)
 }
 
-fn keyword_half(input: &str) -> NomParseResult<String> {
+fn keyword_half(input: Input<'_>) -> NomParseResult<'_, String> {
     between('\'', '\'', take_till(|x| x == '\''))
-        .map(|x| x.to_string())
+        .map(|x: Input<'_>| x.to_string())
         .parse(input)
 }
 
-fn keyword(input: &str) -> NomParseResult<ExprU> {
-    alt((
-        separated_pair(keyword_half, tag("/"), keyword_half).map(|(name, id)| KeywordU {
-            name: name.clone(),
-            id: id.clone(),
-        }),
-        keyword_half.map(|name| KeywordU {
-            name: name.clone(),
-            id: name.clone(),
-        }),
-    ))(input)
+fn keyword(input: Input<'_>) -> NomParseResult<'_, ExprU> {
+    spanned(alt((
+        separated_pair(keyword_half, tag("/"), keyword_half),
+        keyword_half.map(|name| (name.clone(), name)),
+    )))
+    .map(|((name, id), span)| KeywordU { name, id, span })
+    .parse(input)
 }
 
-fn string(input: &str) -> NomParseResult<String> {
+/// a keyword can be followed by the ids it used to have, like `(was 'p', 'pic')`
+fn was<'a, F>(mut keyword: F) -> impl FnMut(Input<'a>) -> NomParseResult<'a, ExprU>
+where
+    F: Parser<Input<'a>, ExprU, NomParseError<Input<'a>>>,
+{
+    move |input: Input<'a>| {
+        let (input, kw) = keyword.parse(input)?;
+        let Ok((rest, _)) =
+            tuple((line_space0, char('('), line_space0, tag("was"), line_space1)).parse(input)
+        else {
+            return Ok((input, kw));
+        };
+        // anything after `(was` has to be the former ids
+        let mut ids = terminated(
+            sep_by1(
+                delimited(line_space0, tag(","), line_space0),
+                expecting("a former id", keyword_half),
+            ),
+            pair(line_space0, expecting_one_of(&["`,`", "`)`"], char(')'))),
+        );
+        match ids.parse(rest) {
+            Ok((rest, ids)) => {
+                let span = Span {
+                    start: kw.span().start,
+                    end: rest.location_offset(),
+                };
+                Ok((
+                    rest,
+                    WasU {
+                        keyword: Box::new(kw),
+                        ids,
+                        span,
+                    },
+                ))
+            }
+            Err(Err::Error(e)) => Err(Err::Failure(e)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// a keyword followed by a list has the keywords in the list nested under it
+fn nested<'a, F>(mut keyword: F) -> impl FnMut(Input<'a>) -> NomParseResult<'a, ExprU>
+where
+    F: Parser<Input<'a>, ExprU, NomParseError<Input<'a>>>,
+{
+    move |input: Input<'a>| {
+        let (input, parent) = keyword.parse(input)?;
+        match preceded(line_space0, list).parse(input) {
+            Ok((input, children)) => {
+                let span = Span {
+                    start: parent.span().start,
+                    end: children.span().end,
+                };
+                Ok((
+                    input,
+                    NestedU {
+                        parent: Box::new(parent),
+                        children: Box::new(children),
+                        span,
+                    },
+                ))
+            }
+            Err(Err::Error(_)) => Ok((input, parent)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn string(input: Input<'_>) -> NomParseResult<'_, String> {
     between('"', '"', take_till(|x| x == '"'))
-        .map(|x| x.to_string())
+        .map(|x: Input<'_>| x.to_string())
         .parse(input)
 }
 
-fn nat(input: &str) -> NomParseResult<u8> {
+fn nat(input: Input<'_>) -> NomParseResult<'_, u8> {
     u8(input)
 }
 
-fn indent(input: &str) -> NomParseResult<&str> {
+fn indent(input: Input<'_>) -> NomParseResult<'_, &str> {
     alt((tag("  "), tag("\t")))(input).map(|(rest, _)| (rest, ""))
 }
 
-fn line_space1(input: &str) -> NomParseResult<&str> {
-    many1(alt((char(' '), char('\t'), char('\n'))))(input).map(|(rest, _)| (rest, ""))
+fn line_space1(input: Input<'_>) -> NomParseResult<'_, &str> {
+    many1(alt((value((), one_of(" \t\n")), value((), comment))))(input).map(|(rest, _)| (rest, ""))
+}
+
+fn line_space0(input: Input<'_>) -> NomParseResult<'_, &str> {
+    many0(alt((value((), one_of(" \t\n")), value((), comment))))(input).map(|(rest, _)| (rest, ""))
 }
 
-fn line_space0(input: &str) -> NomParseResult<&str> {
-    many0(alt((char(' '), char('\t'), char('\n'))))(input).map(|(rest, _)| (rest, ""))
+/// spaces and comments that stay on the current line
+fn inline_space1(input: Input<'_>) -> NomParseResult<'_, &str> {
+    many1(alt((value((), one_of(" \t")), value((), comment))))(input).map(|(rest, _)| (rest, ""))
 }
 
-fn parens<'a, F, O, E: ParseError<&'a str>>(inner: F) -> impl Parser<&'a str, O, E>
+fn inline_space0(input: Input<'_>) -> NomParseResult<'_, &str> {
+    many0(alt((value((), one_of(" \t")), value((), comment))))(input).map(|(rest, _)| (rest, ""))
+}
+
+/// `-- to the end of the line` or `{- a block that can span lines -}`. Block comments can be nested.
+fn comment(input: Input<'_>) -> NomParseResult<'_, &str> {
+    match comment_len(input.fragment()) {
+        Some(len) => Ok((input.take_split(len).0, "")),
+        None if input.fragment().starts_with("{-") => {
+            let (end, _) = input.take_split(input.len());
+            Err(Err::Failure(NomParseError::Expected(end, vec!["`-}`"])))
+        }
+        None => Err(Err::Error(NomParseError::Nom(input, ErrorKind::Tag))),
+    }
+}
+
+/// the length in bytes of the comment at the start of `s`, if there is one and it ends
+fn comment_len(s: &str) -> Option<usize> {
+    if s.starts_with("--") {
+        return Some(s.find('\n').unwrap_or(s.len()));
+    }
+    if !s.starts_with("{-") {
+        return None;
+    }
+    let bytes = s.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        match &bytes[i..i + 2] {
+            b"{-" => {
+                depth += 1;
+                i += 2;
+            }
+            b"-}" => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// when a list element doesn't parse, records the error and skips to the next element
+/// so one mistake doesn't hide the ones after it
+fn recovering<'a, F>(mut inner: F) -> impl FnMut(Input<'a>) -> NomParseResult<'a, ExprU>
 where
-    F: Parser<&'a str, O, E>,
+    F: Parser<Input<'a>, ExprU, NomParseError<Input<'a>>>,
 {
-    between('(', ')', inner)
+    move |input: Input<'a>| match inner.parse(input) {
+        Err(Err::Error(e) | Err::Failure(e)) if skip_element(input).is_some() => {
+            let (rest, skipped) = skip_element(input).unwrap();
+            input.extra.borrow_mut().push(e.recovered());
+            let start = skipped.location_offset() + skipped.len() - skipped.trim_start().len();
+            let end = skipped.location_offset() + skipped.trim_end().len();
+            Ok((rest, ErrorU(Span { start, end })))
+        }
+        result => result,
+    }
+}
+
+/// splits off the input up to the `,` or `]` that ends the current list element.
+/// Fails when there's nothing to skip or the element never ends.
+fn skip_element(input: Input<'_>) -> Option<(Input<'_>, Input<'_>)> {
+    let s = input.fragment();
+    // there's only something to skip if it's more than whitespace and comments
+    let mut skipped = false;
+    let end = |i, skipped: bool| skipped.then(|| input.take_split(i));
+    let mut open = vec![];
+    let mut quote = None;
+    let mut i = 0;
+    while let Some(c) = s[i..].chars().next() {
+        if quote.is_none() && (s[i..].starts_with("--") || s[i..].starts_with("{-")) {
+            // quotes and brackets in comments don't count
+            i += comment_len(&s[i..])?;
+            continue;
+        }
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(' | '[') => open.push(c),
+            (None, ')') if open.last() == Some(&'(') => {
+                open.pop();
+            }
+            (None, ']') if open.last() == Some(&'[') => {
+                open.pop();
+            }
+            // a `]` that doesn't close a bracket in the element closes the list, even if a paren was left open
+            (None, ']') => return end(i, skipped),
+            (None, ',') if open.is_empty() => return end(i, skipped),
+            _ => (),
+        }
+        skipped |= !c.is_whitespace();
+        i += c.len_utf8();
+    }
+    None
+}
+
+/// pairs the output with the span of input the parser consumed
+fn spanned<'a, O, F>(mut inner: F) -> impl FnMut(Input<'a>) -> NomParseResult<'a, (O, Span)>
+where
+    F: Parser<Input<'a>, O, NomParseError<Input<'a>>>,
+{
+    move |input: Input<'a>| {
+        let (rest, x) = inner.parse(input)?;
+        let span = Span {
+            start: input.location_offset(),
+            end: rest.location_offset(),
+        };
+        Ok((rest, (x, span)))
+    }
+}
+
+/// labels the parser with what it accepts when it fails without consuming anything
+fn expecting<'a, O, F>(
+    label: &'static str,
+    mut inner: F,
+) -> impl FnMut(Input<'a>) -> NomParseResult<'a, O>
+where
+    F: Parser<Input<'a>, O, NomParseError<Input<'a>>>,
+{
+    move |input: Input<'a>| match inner.parse(input) {
+        Err(Err::Error(e)) if e.offset() <= input.location_offset() => {
+            Err(Err::Error(NomParseError::Expected(input, vec![label])))
+        }
+        result => result,
+    }
+}
+
+fn expecting_one_of<'a, O, F>(
+    labels: &'static [&'static str],
+    mut inner: F,
+) -> impl FnMut(Input<'a>) -> NomParseResult<'a, O>
+where
+    F: Parser<Input<'a>, O, NomParseError<Input<'a>>>,
+{
+    move |input: Input<'a>| match inner.parse(input) {
+        Err(Err::Error(e)) if e.offset() <= input.location_offset() => {
+            Err(Err::Error(NomParseError::Expected(input, labels.to_vec())))
+        }
+        result => result,
+    }
+}
+
+/// once the parser has made it past its first token a failure is an error in the source
+/// rather than a sign to try something else, so it shouldn't be swallowed by lists of values.
+fn committed<'a, O, F>(mut inner: F) -> impl FnMut(Input<'a>) -> NomParseResult<'a, O>
+where
+    F: Parser<Input<'a>, O, NomParseError<Input<'a>>>,
+{
+    move |input: Input<'a>| match inner.parse(input) {
+        Err(Err::Error(e)) if e.offset() > input.location_offset() => Err(Err::Failure(e)),
+        result => result,
+    }
+}
+
+fn parens<'a, F, O>(inner: F) -> impl Parser<Input<'a>, O, NomParseError<Input<'a>>>
+where
+    F: Parser<Input<'a>, O, NomParseError<Input<'a>>>,
+{
+    terminated(preceded(char('('), inner), expecting("`)`", char(')')))
 }
 
 /// takes two characters and returns a parser for the str between them.
-fn between<'a, O, E: ParseError<&'a str>, F>(
+fn between<'a, O, E: ParseError<Input<'a>>, F>(
     l: char,
     r: char,
     inner: F,
-) -> impl Parser<&'a str, O, E>
+) -> impl Parser<Input<'a>, O, E>
 where
-    F: Parser<&'a str, O, E>,
+    F: Parser<Input<'a>, O, E>,
 {
     terminated(preceded(char(l), inner), char(r))
 }
 
-fn sep_by0<I, O1, O2: Clone, E: ParseError<I>, F, G>(sep: F, value: G) -> impl Parser<I, Vec<O2>, E>
-where
-    I: InputLength + Clone,
-    F: Parser<I, O1, E>,
-    G: Parser<I, O2, E>,
-{
-    alt((sep_by1(sep, value), success(vec![])))
-}
-
 // inspired by the implementation of many0
 fn sep_by1<I, O1, O2, E: ParseError<I>, F, G>(
     mut sep: F,
@@ -230,20 +674,50 @@ where
     }
 }
 
-/// requries trailing whitespace, newline, or end of input
-fn lexeme_vert_allowed<'a, F, O>(inner: F) -> impl Parser<&'a str, O, NomParseError<&'a str>>
+/// requries trailing whitespace, newline, or end of input. Lines after a newline must be indented,
+/// though blank lines and lines with only comments can come first.
+fn lexeme_vert_allowed<'a, F, O>(inner: F) -> impl Parser<Input<'a>, O, NomParseError<Input<'a>>>
 where
-    F: Parser<&'a str, O, NomParseError<&'a str>>,
+    F: Parser<Input<'a>, O, NomParseError<Input<'a>>>,
 {
     terminated(
         inner,
         alt((
-            pair(pair(space0, many1(newline)), indent).map(|_| ""),
-            alt((space1, eof)),
+            tuple((
+                inline_space0,
+                newline,
+                many0(pair(inline_space0, newline)),
+                indent,
+            ))
+            .map(|_| ""),
+            alt((inline_space1, eof.map(|_| ""))),
         )),
     )
 }
 
+/// runs a parser on a str and returns the leftover input as a str to keep tests readable
+#[cfg(test)]
+fn run<'a, O>(
+    mut parser: impl FnMut(Input<'a>) -> NomParseResult<'a, O>,
+    input: &'a str,
+) -> StdResult<(&'a str, O), nom::Err<NomParseError<Input<'a>>>> {
+    // tests are short lived so the recovered errors can live for the rest of the run
+    let recovered = Box::leak(Box::new(RefCell::new(vec![])));
+    parser(Input::new_extra(input, recovered)).map(|(rest, x)| (*rest.fragment(), x))
+}
+
+/// compares expressions without the spans that would otherwise clutter every test
+#[cfg(test)]
+fn run_expr<'a>(
+    parser: impl FnMut(Input<'a>) -> NomParseResult<'a, ExprU>,
+    input: &'a str,
+) -> StdResult<(&'a str, ExprU), nom::Err<NomParseError<Input<'a>>>> {
+    run(parser, input).map(|(rest, x)| (rest, x.without_spans()))
+}
+
+#[cfg(test)]
+const NO_SPAN: Span = Span { start: 0, end: 0 };
+
 #[test]
 fn top_level() {
     //   let input = r#"schema "-" "_"
@@ -253,110 +727,224 @@ fn top_level() {
     let input = r#"schema "-" "_" [ category "Media" (exactly 1) ['art', 'photo'/'ph', 'video'/'v'], category "People" (at_least 0) ['nate']]"#;
 
     let expr = FnU {
+        span: NO_SPAN,
         name: "schema".to_string(),
         args: vec![
-            StringU("-".to_string()),
-            StringU("_".to_string()),
-            ListU(vec![
-                FnU {
-                    name: "category".to_string(),
-                    args: vec![
-                        StringU("Media".to_string()),
-                        FnU {
-                            name: "exactly".to_string(),
-                            args: vec![NatU(1)],
-                        },
-                        ListU(vec![
-                            KeywordU {
-                                name: "art".to_string(),
-                                id: "art".to_string(),
+            StringU("-".to_string(), NO_SPAN),
+            StringU("_".to_string(), NO_SPAN),
+            ListU(
+                vec![
+                    FnU {
+                        span: NO_SPAN,
+                        name: "category".to_string(),
+                        args: vec![
+                            StringU("Media".to_string(), NO_SPAN),
+                            FnU {
+                                span: NO_SPAN,
+                                name: "exactly".to_string(),
+                                args: vec![NatU(1, NO_SPAN)],
                             },
-                            KeywordU {
-                                name: "photo".to_string(),
-                                id: "ph".to_string(),
+                            ListU(
+                                vec![
+                                    KeywordU {
+                                        span: NO_SPAN,
+                                        name: "art".to_string(),
+                                        id: "art".to_string(),
+                                    },
+                                    KeywordU {
+                                        span: NO_SPAN,
+                                        name: "photo".to_string(),
+                                        id: "ph".to_string(),
+                                    },
+                                    KeywordU {
+                                        span: NO_SPAN,
+                                        name: "video".to_string(),
+                                        id: "v".to_string(),
+                                    },
+                                ],
+                                NO_SPAN,
+                            ),
+                        ],
+                    },
+                    FnU {
+                        span: NO_SPAN,
+                        name: "category".to_string(),
+                        args: vec![
+                            StringU("People".to_string(), NO_SPAN),
+                            FnU {
+                                span: NO_SPAN,
+                                name: "at_least".to_string(),
+                                args: vec![NatU(0, NO_SPAN)],
                             },
-                            KeywordU {
-                                name: "video".to_string(),
-                                id: "v".to_string(),
-                            },
-                        ]),
-                    ],
-                },
-                FnU {
-                    name: "category".to_string(),
-                    args: vec![
-                        StringU("People".to_string()),
-                        FnU {
-                            name: "at_least".to_string(),
-                            args: vec![NatU(0)],
-                        },
-                        ListU(vec![KeywordU {
-                            name: "nate".to_string(),
-                            id: "nate".to_string(),
-                        }]),
-                    ],
-                },
-            ]),
+                            ListU(
+                                vec![KeywordU {
+                                    span: NO_SPAN,
+                                    name: "nate".to_string(),
+                                    id: "nate".to_string(),
+                                }],
+                                NO_SPAN,
+                            ),
+                        ],
+                    },
+                ],
+                NO_SPAN,
+            ),
         ],
     };
 
-    assert_eq!(Ok(expr), parse(input));
+    assert_eq!(Ok(expr), parse(input).map(ExprU::without_spans));
+}
+
+#[test]
+fn parse_errors() {
+    let err = |input: &str| match parse(input).as_ref().map_err(Vec::as_slice) {
+        Err([SchemaParseError::UnexpectedInput { location, expected }]) => {
+            (location.line, location.column, expected.clone())
+        }
+        other => panic!("expected a parse error but got {other:?}"),
+    };
+    assert_eq!(
+        err("schema \"-\" \"_\"\n  [ category \"Media\" (exactly 1) ['art' 'v']\n  ]"),
+        (2, 41, vec!["`,`".to_string(), "`]`".to_string()])
+    );
+    assert_eq!(
+        err("schema \"-\" \"_\" []]"),
+        (1, 18, vec!["end of input".to_string()])
+    );
+    assert_eq!(err("").2.len(), 9);
+    // keywords are single quoted, and double quoted ones are pointed at instead of ending the arguments early
+    assert_eq!(
+        err(r#"schema "-" "_" [ category "Media" (exactly 1) ["art", "photo"/"ph"] ]"#),
+        (1, 62, vec!["`,`".to_string(), "`]`".to_string()])
+    );
+
+    assert_eq!(
+        parse("schema \"-\" \"_\"\n\t[ 'a' 'b' ]").unwrap_err()[0].to_string(),
+        "Expected one of `,` or `]`\n --> 2:8\n  |\n2 | \t[ 'a' 'b' ]\n  | \t      ^"
+    );
+}
+
+#[test]
+fn parse_recovers_from_bad_elements() {
+    let input = "schema \"-\" \"_\"\n  [ category \"Media\" (exactly +) ['art']\n  , category \"People\" (at_least 0) ['nate' 'al']\n  , category \"Place\" (at_most 1) ['home', (]\n  ]";
+    let (expr, errors) = parse_recovering(input);
+    let lines: Vec<u32> = errors
+        .iter()
+        .map(|e| match e {
+            SchemaParseError::UnexpectedInput { location, .. } => location.line,
+            e => panic!("unexpected error {e:?}"),
+        })
+        .collect();
+    assert_eq!(lines, vec![2, 3, 4]);
+
+    // the bad elements are left as holes so the rest can still be typechecked
+    let Some(FnU { args, .. }) = expr else {
+        panic!("expected the schema to be recovered");
+    };
+    let ListU(categories, _) = &args[2] else {
+        panic!("expected a list of categories");
+    };
+    assert!(matches!(categories[0], ErrorU(_)));
+    assert!(matches!(categories[1], ErrorU(_)));
+    let FnU { args, .. } = &categories[2] else {
+        panic!("expected the last category to parse");
+    };
+    assert!(matches!(&args[2], ListU(kws, _) if matches!(kws[..], [KeywordU { .. }, ErrorU(_)])));
+
+    // nothing to skip to
+    assert_eq!(parse_recovering("schema \"-\" \"_\" [ (").1.len(), 1);
 }
 
 #[test]
 fn parse_parens() {
-    assert_eq!(expr("(0)"), Ok(("", NatU(0))));
-    assert_eq!(expr("((0))"), Ok(("", NatU(0))));
-    assert_eq!(expr("([(0)])"), Ok(("", ListU(vec![NatU(0)]))));
+    assert_eq!(run_expr(expr, "(0)"), Ok(("", NatU(0, NO_SPAN))));
+    assert_eq!(run_expr(expr, "((0))"), Ok(("", NatU(0, NO_SPAN))));
+    assert_eq!(
+        run_expr(expr, "([(0)])"),
+        Ok(("", ListU(vec![NatU(0, NO_SPAN)], NO_SPAN)))
+    );
 }
 
 #[test]
 fn parse_func() {
     let foo0 = FnU {
+        span: NO_SPAN,
         name: "foo".to_string(),
-        args: vec![NatU(0)],
+        args: vec![NatU(0, NO_SPAN)],
     };
 
     let fool01 = FnU {
+        span: NO_SPAN,
         name: "foo".to_string(),
-        args: vec![ListU(vec![NatU(0), NatU(1)])],
+        args: vec![ListU(vec![NatU(0, NO_SPAN), NatU(1, NO_SPAN)], NO_SPAN)],
     };
 
     let fool = FnU {
+        span: NO_SPAN,
         name: "foo".to_string(),
-        args: vec![ListU(vec![])],
+        args: vec![ListU(vec![], NO_SPAN)],
     };
 
     let foo99l01 = FnU {
+        span: NO_SPAN,
         name: "foo".to_string(),
-        args: vec![NatU(99), ListU(vec![NatU(0), NatU(1)])],
+        args: vec![
+            NatU(99, NO_SPAN),
+            ListU(vec![NatU(0, NO_SPAN), NatU(1, NO_SPAN)], NO_SPAN),
+        ],
     };
 
     let foo9999ll = FnU {
+        span: NO_SPAN,
         name: "foo".to_string(),
-        args: vec![NatU(99), NatU(99), ListU(vec![ListU(vec![])])],
+        args: vec![
+            NatU(99, NO_SPAN),
+            NatU(99, NO_SPAN),
+            ListU(vec![ListU(vec![], NO_SPAN)], NO_SPAN),
+        ],
     };
 
-    assert_eq!(func("foo 0"), Ok(("", foo0.clone())));
-    assert_eq!(func("foo []"), Ok(("", fool.clone())));
-    assert_eq!(func("foo\n  0"), Ok(("", foo0.clone())));
-    assert_eq!(func("foo\n\t0"), Ok(("", foo0.clone())));
-    assert_eq!(func("foo \n  0"), Ok(("", foo0.clone())));
-    assert_eq!(func("foo \n  [ 0\n  , 1\n  ]"), Ok(("", fool01.clone())));
+    assert_eq!(run_expr(func, "foo 0"), Ok(("", foo0.clone())));
+    assert_eq!(run_expr(func, "foo []"), Ok(("", fool.clone())));
+    assert_eq!(run_expr(func, "foo\n  0"), Ok(("", foo0.clone())));
+    assert_eq!(run_expr(func, "foo\n\t0"), Ok(("", foo0.clone())));
+    assert_eq!(run_expr(func, "foo \n  0"), Ok(("", foo0.clone())));
     assert_eq!(
-        func("foo 99\n  [ 0\n  , 1\n  ]"),
+        run_expr(func, "foo \n  [ 0\n  , 1\n  ]"),
+        Ok(("", fool01.clone()))
+    );
+    assert_eq!(
+        run_expr(func, "foo 99\n  [ 0\n  , 1\n  ]"),
         Ok(("", foo99l01.clone()))
     );
-    assert_eq!(func("foo\n  [  0\n  , 1\n  ]"), Ok(("", fool01.clone())));
-    assert_eq!(func("foo 99 99\n  [  []  ]"), Ok(("", foo9999ll.clone())));
-    assert!(func(r#"category "Media" (exactly 1) ["art", "photo"/"ph", "video"/"v"]"#).is_ok());
     assert_eq!(
-        func("boop []]"),
+        run_expr(func, "foo\n  [  0\n  , 1\n  ]"),
+        Ok(("", fool01.clone()))
+    );
+    assert_eq!(
+        run_expr(func, "foo 99 99\n  [  []  ]"),
+        Ok(("", foo9999ll.clone()))
+    );
+    assert!(run(
+        func,
+        r#"category "Media" (exactly 1) ['art', 'photo'/'ph', 'video'/'v']"#
+    )
+    .is_ok());
+    // errors inside arguments aren't mistaken for the end of the argument list. Double quoted keywords
+    // never parsed as part of a schema, but this used to stop before them and succeed.
+    assert!(run(
+        func,
+        r#"category "Media" (exactly 1) ["art", "photo"/"ph", "video"/"v"]"#
+    )
+    .is_err());
+    assert_eq!(
+        run_expr(func, "boop []]"),
         Ok((
             "]",
             FnU {
+                span: NO_SPAN,
                 name: "boop".to_string(),
-                args: vec![ListU(vec![])]
+                args: vec![ListU(vec![], NO_SPAN)]
             }
         ))
     );
@@ -364,45 +952,138 @@ fn parse_func() {
 
 #[test]
 fn parse_list() {
-    assert_eq!(list("[]"), Ok(("", ListU(vec![]))));
-    assert_eq!(list("[[]]"), Ok(("", ListU(vec![ListU(vec![])]))));
-    assert_eq!(list("[0,1]"), Ok(("", ListU(vec![NatU(0), NatU(1)]))));
-    assert_eq!(list("[0, 1]"), Ok(("", ListU(vec![NatU(0), NatU(1)]))));
-    assert_eq!(list("[ 0 ]"), Ok(("", ListU(vec![NatU(0)]))));
-    assert_eq!(list("[ ]"), Ok(("", ListU(vec![]))));
-    assert_eq!(list("[\n\t]"), Ok(("", ListU(vec![]))));
-    assert_eq!(list("[ 0\n\t]"), Ok(("", ListU(vec![NatU(0)]))));
-    assert_eq!(list("[ 0\n, 1\n]"), Ok(("", ListU(vec![NatU(0), NatU(1)]))));
-    assert_eq!(list("[]]"), Ok(("]", ListU(vec![]))));
-    assert_eq!(
-        list("[boop []]"),
+    assert_eq!(run_expr(list, "[]"), Ok(("", ListU(vec![], NO_SPAN))));
+    assert_eq!(
+        run_expr(list, "[[]]"),
+        Ok(("", ListU(vec![ListU(vec![], NO_SPAN)], NO_SPAN)))
+    );
+    assert_eq!(
+        run_expr(list, "[0,1]"),
+        Ok(("", ListU(vec![NatU(0, NO_SPAN), NatU(1, NO_SPAN)], NO_SPAN)))
+    );
+    assert_eq!(
+        run_expr(list, "[0, 1]"),
+        Ok(("", ListU(vec![NatU(0, NO_SPAN), NatU(1, NO_SPAN)], NO_SPAN)))
+    );
+    assert_eq!(
+        run_expr(list, "[ 0 ]"),
+        Ok(("", ListU(vec![NatU(0, NO_SPAN)], NO_SPAN)))
+    );
+    assert_eq!(run_expr(list, "[ ]"), Ok(("", ListU(vec![], NO_SPAN))));
+    assert_eq!(run_expr(list, "[\n\t]"), Ok(("", ListU(vec![], NO_SPAN))));
+    assert_eq!(
+        run_expr(list, "[ 0\n\t]"),
+        Ok(("", ListU(vec![NatU(0, NO_SPAN)], NO_SPAN)))
+    );
+    assert_eq!(
+        run_expr(list, "[ 0\n, 1\n]"),
+        Ok(("", ListU(vec![NatU(0, NO_SPAN), NatU(1, NO_SPAN)], NO_SPAN)))
+    );
+    assert_eq!(run_expr(list, "[]]"), Ok(("]", ListU(vec![], NO_SPAN))));
+    assert_eq!(
+        run_expr(list, "[boop []]"),
         Ok((
             "",
-            ListU(vec![FnU {
-                name: "boop".to_string(),
-                args: vec![ListU(vec![])]
-            }])
+            ListU(
+                vec![FnU {
+                    span: NO_SPAN,
+                    name: "boop".to_string(),
+                    args: vec![ListU(vec![], NO_SPAN)]
+                }],
+                NO_SPAN
+            )
+        ))
+    );
+}
+
+#[test]
+fn parse_let() {
+    let var = |name: &str| VarU(name.to_string(), NO_SPAN);
+    let concat = |l, r| FnU {
+        span: NO_SPAN,
+        name: "++".to_string(),
+        args: vec![l, r],
+    };
+    assert_eq!(
+        run_expr(expr, "let xs = [] in\nfoo (xs ++ [0])"),
+        Ok((
+            "",
+            LetU {
+                name: "xs".to_string(),
+                value: Box::new(ListU(vec![], NO_SPAN)),
+                body: Box::new(FnU {
+                    span: NO_SPAN,
+                    name: "foo".to_string(),
+                    args: vec![concat(var("xs"), ListU(vec![NatU(0, NO_SPAN)], NO_SPAN))],
+                }),
+                span: NO_SPAN,
+            }
+        ))
+    );
+    // `++` groups to the left
+    assert_eq!(
+        run_expr(expr, "a ++ b\n  ++ c"),
+        Ok(("", concat(concat(var("a"), var("b")), var("c"))))
+    );
+    assert_eq!(run_expr(expr, "letter"), Ok(("", var("letter"))));
+    assert_eq!(
+        run_expr(expr, "import \"../people.q\" ++ xs"),
+        Ok((
+            "",
+            concat(
+                ImportU {
+                    path: "../people.q".to_string(),
+                    name: None,
+                    resolved: None,
+                    span: NO_SPAN
+                },
+                var("xs")
+            )
+        ))
+    );
+    assert_eq!(
+        run_expr(expr, "import family from \"../people.q\""),
+        Ok((
+            "",
+            ImportU {
+                path: "../people.q".to_string(),
+                name: Some("family".to_string()),
+                resolved: None,
+                span: NO_SPAN
+            }
         ))
     );
+    assert_eq!(run_expr(expr, "important"), Ok(("", var("important"))));
+    assert!(run(expr, "let in = [] in in").is_err());
+    assert!(run(expr, "a ++ ]").is_err());
+
+    let input = "let xs = ['a'] in\nschema \"-\" \"_\" [ category \"A\" (exactly 1) xs ]";
+    assert!(parse(input).is_ok());
+    assert_eq!(
+        parse("let xs = ['a']\nschema \"-\" \"_\" []").unwrap_err()[0].to_string(),
+        "Expected `in`\n --> 2:1\n  |\n2 | schema \"-\" \"_\" []\n  | ^"
+    );
 }
 
 #[test]
 fn parse_keyword() {
     assert_eq!(
-        keyword(r#"'abc'/'a'"#),
+        run_expr(keyword, r#"'abc'/'a'"#),
         Ok((
             "",
             KeywordU {
+                span: NO_SPAN,
                 name: "abc".to_string(),
                 id: "a".to_string()
             }
         ))
     );
     assert_eq!(
-        keyword(r#"'abc'"#),
+        run_expr(keyword, r#"'abc'"#),
         Ok((
             "",
             KeywordU {
+                span: NO_SPAN,
                 name: "abc".to_string(),
                 id: "abc".to_string()
             }
@@ -410,16 +1091,160 @@ fn parse_keyword() {
     );
 }
 
+#[test]
+fn parse_nested_keyword() {
+    let kw = |name: &str, id: &str| KeywordU {
+        span: NO_SPAN,
+        name: name.to_string(),
+        id: id.to_string(),
+    };
+    let nested = |parent, children| NestedU {
+        parent: Box::new(parent),
+        children: Box::new(ListU(children, NO_SPAN)),
+        span: NO_SPAN,
+    };
+    assert_eq!(
+        run_expr(expr, "['animal' ['dog'/'d', 'bird' ['owl']], 'plant']"),
+        Ok((
+            "",
+            ListU(
+                vec![
+                    nested(
+                        kw("animal", "animal"),
+                        vec![
+                            kw("dog", "d"),
+                            nested(kw("bird", "bird"), vec![kw("owl", "owl")])
+                        ]
+                    ),
+                    kw("plant", "plant")
+                ],
+                NO_SPAN
+            )
+        ))
+    );
+    // children can go on the next line
+    assert!(matches!(
+        run_expr(expr, "'animal'\n  [ 'dog' ]"),
+        Ok(("", NestedU { .. }))
+    ));
+    assert!(
+        parse("schema \"-\" \"_\" [ category \"A\" (at_least 0) ['animal' ['dog' 'cat']] ]")
+            .is_err()
+    );
+}
+
+#[test]
+fn parse_former_ids() {
+    let photo = KeywordU {
+        span: NO_SPAN,
+        name: "photo".to_string(),
+        id: "ph".to_string(),
+    };
+    assert_eq!(
+        run_expr(expr, "'photo'/'ph' (was 'p', 'pic')"),
+        Ok((
+            "",
+            WasU {
+                keyword: Box::new(photo.clone()),
+                ids: vec!["p".to_string(), "pic".to_string()],
+                span: NO_SPAN
+            }
+        ))
+    );
+    assert!(matches!(
+        run_expr(expr, "'photo'/'ph' (was 'p') ['portrait']"),
+        Ok(("", NestedU { parent, .. })) if matches!(*parent, WasU { .. })
+    ));
+    // anything else in parentheses is a separate argument
+    assert!(matches!(
+        run_expr(expr, "when 'photo' (category \"A\" (exactly 1) ['a'])"),
+        Ok(("", FnU { args, .. })) if args.len() == 2
+    ));
+    assert!(run(expr, "'photo' (was p)").is_err());
+}
+
 #[test]
 fn parse_string() {
-    assert_eq!(string(r#""abc""#), Ok(("", "abc".to_string())));
+    assert_eq!(run(string, r#""abc""#), Ok(("", "abc".to_string())));
+}
+
+#[test]
+fn parse_func_comments() {
+    let foo0 = FnU {
+        span: NO_SPAN,
+        name: "foo".to_string(),
+        args: vec![NatU(0, NO_SPAN)],
+    };
+    let foo01 = FnU {
+        span: NO_SPAN,
+        name: "foo".to_string(),
+        args: vec![NatU(0, NO_SPAN), NatU(1, NO_SPAN)],
+    };
+
+    assert_eq!(run_expr(func, "foo {- zero -} 0"), Ok(("", foo0.clone())));
+    assert_eq!(run_expr(func, "foo -- zero\n  0"), Ok(("", foo0.clone())));
+    assert_eq!(
+        run_expr(func, "foo\n  -- zero\n\n  0"),
+        Ok(("", foo0.clone()))
+    );
+    assert_eq!(run_expr(func, "foo 0 -- trailing"), Ok(("", foo0.clone())));
+    assert_eq!(
+        run_expr(func, "foo 0\n  -- one\n  1"),
+        Ok(("", foo01.clone()))
+    );
+    assert_eq!(
+        run_expr(func, "foo 0 {- {- nested -} -} 1"),
+        Ok(("", foo01))
+    );
+    // continuation lines still have to be indented after a comment
+    assert_eq!(
+        run_expr(expr, "foo -- zero\n0"),
+        Ok((" -- zero\n0", VarU("foo".to_string(), NO_SPAN)))
+    );
+}
+
+#[test]
+fn parse_list_comments() {
+    let l01 = ListU(vec![NatU(0, NO_SPAN), NatU(1, NO_SPAN)], NO_SPAN);
+    assert_eq!(
+        run_expr(list, "[ -- nothing\n]"),
+        Ok(("", ListU(vec![], NO_SPAN)))
+    );
+    assert_eq!(run_expr(list, "[0, {- one -} 1]"), Ok(("", l01.clone())));
+    assert_eq!(
+        run_expr(list, "[ 0 -- zero\n, 1 -- one\n]"),
+        Ok(("", l01.clone()))
+    );
+    assert_eq!(
+        run_expr(list, "[ 0\n  {- a comment\n     over lines -}\n, 1\n]"),
+        Ok(("", l01))
+    );
+    // the quote in the comment doesn't stop later elements from being recovered
+    let (_, errors) =
+        parse_recovering("schema \"-\" \"_\" [ (1 2 -- don't\n), category \"a\" (exactly 1) [+] ]");
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn parse_comments() {
+    assert_eq!(run(line_space0, "-- a\n  x"), Ok(("x", "")));
+    assert_eq!(run(line_space1, " {- a -}x"), Ok(("x", "")));
+    assert_eq!(run(comment, "-- a\nx"), Ok(("\nx", "")));
+    assert_eq!(run(comment, "{- a {- b -} c -}x"), Ok(("x", "")));
+    assert!(matches!(
+        run(comment, "{- a {- b -}"),
+        Err(Err::Failure(NomParseError::Expected(_, _)))
+    ));
+    // comment markers in strings are part of the string
+    assert_eq!(run(string, "\"a -- b\""), Ok(("", "a -- b".to_string())));
+    assert!(parse("-- the start\nschema \"--\" \"_\" [] -- the end").is_ok());
 }
 
 #[test]
 fn parse_line_space0() {
-    assert_eq!(line_space0(""), Ok(("", "")));
-    assert_eq!(line_space0(" x"), Ok(("x", "")));
-    assert_eq!(line_space0("\n   \t x"), Ok(("x", "")));
+    assert_eq!(run(line_space0, ""), Ok(("", "")));
+    assert_eq!(run(line_space0, " x"), Ok(("x", "")));
+    assert_eq!(run(line_space0, "\n   \t x"), Ok(("x", "")));
 }
 
 #[test]
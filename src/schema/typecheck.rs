@@ -1,25 +1,67 @@
 use super::{
-    Category,
+    analysis, Category, Constraint,
     ExprU::{self, *},
-    Keyword, Requirement,
+    Keyword, KeywordRef, Requirement,
     Requirement::*,
     Schema, SchemaTypeCheckError,
     SchemaTypeCheckError::*,
+    Span, Spanned, ValueKind,
 };
 use std::{collections::HashSet, fmt, result::Result as StdResult};
 use ExprT::*;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 enum ExprT {
-    SchemaT(Schema),
-    CategoryT((Category, Vec<Keyword>)),
-    KeywordT(Keyword),
+    SchemaT(Schema, SchemaSpans),
+    /// a category, its keywords, and the keywords that have to be selected for it to apply
+    CategoryT(Category, Vec<(Keyword, Span)>, Vec<(Keyword, Span)>),
+    /// a rule between two keywords that haven't been looked up in the schema yet
+    ConstraintT(ConstraintKind, [(Keyword, Span); 2]),
+    /// keywords remember where they were written, even when they're used through a variable
+    KeywordT {
+        keyword: Keyword,
+        span: Span,
+        former_ids: Vec<String>,
+        children: Vec<ExprT>,
+    },
     RequirementT(Requirement),
+    /// the most characters a text value can have
+    MaxLenT(u8),
     NatT(u8),
     StringT(String),
     ListT(Vec<ExprT>),
 }
 
+/// where the parts of a schema that are checked after typechecking came from
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SchemaSpans {
+    delim: Span,
+    empty: Span,
+    categories: Vec<Span>,
+    /// by category, then keyword
+    keywords: Vec<Vec<Span>>,
+}
+
+impl SchemaSpans {
+    fn at(&self, part: SchemaPart) -> Span {
+        match part {
+            SchemaPart::Delimiter => self.delim,
+            SchemaPart::Empty => self.empty,
+            SchemaPart::Category(cat) => self.categories[cat],
+            SchemaPart::Keyword(cat, kw) => self.keywords[cat][kw],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ConstraintKind {
+    Requires,
+    Excludes,
+}
+
+/// values bound by `let`, innermost last. A value is `None` when its errors have already been reported.
+type Env = Vec<(String, Option<ExprT>)>;
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Type {
     String,
@@ -29,6 +71,8 @@ pub enum Type {
     Schema,
     Category,
     Requirement,
+    TextLimit,
+    Constraint,
     Hole,
 }
 
@@ -38,140 +82,569 @@ impl fmt::Display for Type {
             Type::String => write!(f, "string"),
             Type::Nat => write!(f, "nat"),
             Type::Keyword => write!(f, "keyword"),
-            Type::List(_) => write!(f, "list"),
+            Type::List(t) => write!(f, "list of {t}"),
             Type::Schema => write!(f, "schema"),
             Type::Category => write!(f, "category"),
             Type::Requirement => write!(f, "requirement"),
+            Type::TextLimit => write!(f, "text limit"),
+            Type::Constraint => write!(f, "constraint"),
             Type::Hole => write!(f, "unknown"),
         }
     }
 }
 
-type Result<T> = StdResult<T, SchemaTypeCheckError>;
+type Result<T> = StdResult<T, Vec<Spanned<SchemaTypeCheckError>>>;
 
+/// checks the schema is well typed and that every name it generates can be decoded
 pub fn typecheck(expr: ExprU) -> Result<Schema> {
-    match typecheck_(expr) {
-        Ok(SchemaT(x)) => Ok(x),
-        Ok(_) => Err(ExpectedTopLevelSchema),
+    let span = expr.span();
+    match typecheck_(expr, &vec![]) {
+        Ok(SchemaT(x, spans)) => {
+            let errors: Vec<_> = super::analysis::check_decodable(&x)
+                .into_iter()
+                .map(|(e, at)| e.at(spans.at(at)))
+                .collect();
+            if errors.is_empty() {
+                Ok(x)
+            } else {
+                Err(errors)
+            }
+        }
+        Ok(_) => Err(vec![ExpectedTopLevelSchema.at(span)]),
         Err(e) => Err(e),
     }
 }
 
-fn typecheck_(expr: ExprU) -> Result<ExprT> {
+/// where in the schema an error found after typechecking is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchemaPart {
+    Delimiter,
+    Empty,
+    Category(usize),
+    /// indices of the category and keyword in the schema
+    Keyword(usize, usize),
+}
+
+fn typecheck_(expr: ExprU, env: &Env) -> Result<ExprT> {
     match expr {
-        NatU(x) => Ok(NatT(x)),
-        StringU(x) => Ok(StringT(x)),
-        KeywordU { name, id } => Ok(KeywordT(Keyword { name, id })),
-        ListU(xs) => {
-            let xs = xs
-                .iter()
-                .map(|x| typecheck_(x.clone()))
-                .collect::<Result<Vec<ExprT>>>()?;
-            let mut types = HashSet::with_capacity(2);
-            for x in xs.clone() {
-                types.insert(type_of(&x));
+        NatU(x, _) => Ok(NatT(x)),
+        StringU(x, _) => Ok(StringT(x)),
+        KeywordU { name, id, span } => Ok(KeywordT {
+            keyword: Keyword { name, id },
+            span,
+            former_ids: vec![],
+            children: vec![],
+        }),
+        WasU {
+            keyword,
+            ids,
+            span: was_span,
+        } => match typecheck_(*keyword, env)? {
+            KeywordT {
+                keyword,
+                span,
+                mut former_ids,
+                children,
+            } => {
+                former_ids.extend(ids);
+                Ok(KeywordT {
+                    keyword,
+                    span,
+                    former_ids,
+                    children,
+                })
             }
-            let types = types.into_iter().collect::<Vec<Type>>();
-            match &types[..] {
-                // empty list can take on any type
-                [] => Ok(ListT(xs)),
-                // homogeneous
-                [_] => Ok(ListT(xs)),
-                // heterogenous
-                _ => Err(HeterogeneousList(types.clone())),
+            x => Err(mismatches(&[(Type::Keyword, &x, was_span)])),
+        },
+        NestedU {
+            parent, children, ..
+        } => {
+            let spans = [parent.span(), children.span()];
+            match &typecheck_all(vec![*parent, *children], env)?[..] {
+                [parent @ KeywordT { .. }, children @ ListT(xs)]
+                    if fits(&keywords_t(), &type_of(children)) =>
+                {
+                    let mut parent = parent.clone();
+                    if let KeywordT { children, .. } = &mut parent {
+                        children.extend(xs.iter().cloned());
+                    }
+                    Ok(parent)
+                }
+                [parent, children] => Err(mismatches(&[
+                    (Type::Keyword, parent, spans[0]),
+                    (keywords_t(), children, spans[1]),
+                ])),
+                _ => unreachable!(),
             }
         }
-        FnU { name, args } => match (name.as_str(), &args[..]) {
-            ("exactly", [NatU(x)]) => Ok(RequirementT(Exactly(*x))),
-            ("at_least", [NatU(x)]) => Ok(RequirementT(AtLeast(*x))),
-            ("at_most", [NatU(x)]) => Ok(RequirementT(AtMost(*x))),
-            ("category", [StringU(name), req @ FnU { .. }, keywords @ ListU(_)]) => {
-                let req = typecheck_(req.clone())?;
-                let keywords = typecheck_(keywords.clone())?;
-                let t = type_of(&keywords);
-                match (req, t.clone(), keywords) {
-                    (RequirementT(requirement), Type::List(t), ListT(xs)) => {
-                        if let Type::Keyword = *t {
-                            let keywords: Vec<Keyword> = xs
-                                .into_iter()
-                                .map(|elem| match elem {
-                                    KeywordT(kw) => kw,
-                                    _ => panic!("unreachable"),
-                                })
-                                .collect();
-                            Ok(CategoryT((
-                                Category {
-                                    name: name.clone(),
-                                    requirement,
-                                },
-                                keywords,
-                            )))
-                        } else {
-                            Err(TypeMismatch {
-                                expected: Type::List(Box::new(Type::Keyword)),
-                                got: Type::List(t),
-                            })
-                        }
-                    }
-                    _ => Err(TypeMismatch {
-                        expected: Type::List(Box::new(Type::Keyword)),
-                        got: t,
-                    }),
+        // the parser has already reported why this didn't parse
+        ErrorU(_) => Err(vec![]),
+        VarU(name, span) => match env.iter().rev().find(|(bound, _)| *bound == name) {
+            Some((_, Some(x))) => Ok(x.clone()),
+            Some((_, None)) => Err(vec![]),
+            None => Err(vec![UnboundVariable(name).at(span)]),
+        },
+        // imported files can't see the names bound where they're imported
+        ImportU {
+            resolved: Some(expr),
+            ..
+        } => typecheck_(*expr, &vec![]),
+        // the file couldn't be imported, which has already been reported
+        ImportU { resolved: None, .. } => Err(vec![]),
+        LetU {
+            name, value, body, ..
+        } => {
+            // errors in the value are reported even if the body never uses it
+            let (value, mut errors) = match typecheck_(*value, env) {
+                Ok(x) => (Some(x), vec![]),
+                Err(es) => (None, es),
+            };
+            let mut env = env.clone();
+            env.push((name, value));
+            match typecheck_(*body, &env) {
+                Ok(x) if errors.is_empty() => Ok(x),
+                Ok(_) => Err(errors),
+                Err(es) => {
+                    errors.extend(es);
+                    Err(errors)
                 }
             }
-            ("schema", [StringU(delim), StringU(empty), categories @ ListU(_)]) => {
-                let categories = typecheck_(categories.clone())?;
-                let t = type_of(&categories);
-                match (t.clone(), categories) {
-                    (Type::List(telem), ListT(xs)) => {
-                        if let Type::Category = *telem {
-                            let categories = xs
-                                .into_iter()
+        }
+        ListU(xs, span) => homogeneous(typecheck_all(xs, env)?, span),
+        FnU { name, args, span } => {
+            let spans: Vec<Span> = args.iter().map(ExprU::span).collect();
+            match (name.as_str(), args.len()) {
+                ("exactly" | "at_least" | "at_most", 1) => {
+                    match typecheck_all(args, env)?.remove(0) {
+                        NatT(x) => Ok(RequirementT(match name.as_str() {
+                            "exactly" => Exactly(x),
+                            "at_least" => AtLeast(x),
+                            _ => AtMost(x),
+                        })),
+                        x => Err(mismatches(&[(Type::Nat, &x, spans[0])])),
+                    }
+                }
+                ("between", 2) => match &typecheck_all(args, env)?[..] {
+                    [NatT(min), NatT(max)] if min <= max => Ok(RequirementT(Between(*min, *max))),
+                    [NatT(min), NatT(max)] => Err(vec![EmptyRange {
+                        min: *min,
+                        max: *max,
+                    }
+                    .at(span)]),
+                    [min, max] => Err(mismatches(&[
+                        (Type::Nat, min, spans[0]),
+                        (Type::Nat, max, spans[1]),
+                    ])),
+                    _ => unreachable!(),
+                },
+                ("++", 2) => match &typecheck_all(args, env)?[..] {
+                    [ListT(xs), ListT(ys)] => {
+                        homogeneous(xs.iter().chain(ys).cloned().collect(), span)
+                    }
+                    [xs, ys] => {
+                        let list = Type::List(Box::new(Type::Hole));
+                        Err(mismatches(&[
+                            (list.clone(), xs, spans[0]),
+                            (list, ys, spans[1]),
+                        ]))
+                    }
+                    _ => unreachable!(),
+                },
+                ("category", 3) => match &typecheck_all(args, env)?[..] {
+                    [StringT(name), RequirementT(requirement), keywords @ ListT(xs)]
+                        if fits(&keywords_t(), &type_of(keywords)) =>
+                    {
+                        let mut category = Category {
+                            name: name.clone(),
+                            requirement: *requirement,
+                            value: None,
+                            parents: vec![],
+                            former_ids: vec![],
+                        };
+                        let mut keywords = vec![];
+                        flatten(xs, None, &mut keywords, &mut category);
+                        Ok(CategoryT(category, keywords, vec![]))
+                    }
+                    [name, req, keywords] => Err(mismatches(&[
+                        (Type::String, name, spans[0]),
+                        (Type::Requirement, req, spans[1]),
+                        (keywords_t(), keywords, spans[2]),
+                    ])),
+                    _ => unreachable!(),
+                },
+                ("date", 2) => match &typecheck_all(args, env)?[..] {
+                    [StringT(name), StringT(format)] if analysis::is_date_format(format) => {
+                        Ok(value_category(name, ValueKind::Date(format.clone())))
+                    }
+                    [StringT(_), StringT(format)] => {
+                        Err(vec![InvalidDateFormat(format.clone()).at(spans[1])])
+                    }
+                    [name, format] => Err(mismatches(&[
+                        (Type::String, name, spans[0]),
+                        (Type::String, format, spans[1]),
+                    ])),
+                    _ => unreachable!(),
+                },
+                ("number", 3) => match &typecheck_all(args, env)?[..] {
+                    [StringT(name), NatT(min), NatT(max)] if min <= max => {
+                        Ok(value_category(name, ValueKind::Number(*min, *max)))
+                    }
+                    [StringT(_), NatT(min), NatT(max)] => Err(vec![EmptyRange {
+                        min: *min,
+                        max: *max,
+                    }
+                    .at(span)]),
+                    [name, min, max] => Err(mismatches(&[
+                        (Type::String, name, spans[0]),
+                        (Type::Nat, min, spans[1]),
+                        (Type::Nat, max, spans[2]),
+                    ])),
+                    _ => unreachable!(),
+                },
+                ("text", 2) => match &typecheck_all(args, env)?[..] {
+                    [StringT(name), MaxLenT(max)] => {
+                        Ok(value_category(name, ValueKind::Text(*max)))
+                    }
+                    [name, max] => Err(mismatches(&[
+                        (Type::String, name, spans[0]),
+                        (Type::TextLimit, max, spans[1]),
+                    ])),
+                    _ => unreachable!(),
+                },
+                ("max_len", 1) => match typecheck_all(args, env)?.remove(0) {
+                    NatT(x) => Ok(MaxLenT(x)),
+                    x => Err(mismatches(&[(Type::Nat, &x, spans[0])])),
+                },
+                ("requires" | "excludes", 2) => match &typecheck_all(args, env)?[..] {
+                    [KeywordT {
+                        keyword: a,
+                        span: a_span,
+                        ..
+                    }, KeywordT {
+                        keyword: b,
+                        span: b_span,
+                        ..
+                    }] => {
+                        let kind = match name.as_str() {
+                            "requires" => ConstraintKind::Requires,
+                            _ => ConstraintKind::Excludes,
+                        };
+                        Ok(ConstraintT(
+                            kind,
+                            [(a.clone(), *a_span), (b.clone(), *b_span)],
+                        ))
+                    }
+                    [a, b] => Err(mismatches(&[
+                        (Type::Keyword, a, spans[0]),
+                        (Type::Keyword, b, spans[1]),
+                    ])),
+                    _ => unreachable!(),
+                },
+                ("when", 2) => match typecheck_all(args, env)?.as_slice() {
+                    [KeywordT {
+                        keyword: kw,
+                        span: kw_span,
+                        ..
+                    }, CategoryT(cat, kws, conditions)] => {
+                        let mut conditions = conditions.clone();
+                        conditions.push((kw.clone(), *kw_span));
+                        Ok(CategoryT(cat.clone(), kws.clone(), conditions))
+                    }
+                    [kw, cat] => Err(mismatches(&[
+                        (Type::Keyword, kw, spans[0]),
+                        (Type::Category, cat, spans[1]),
+                    ])),
+                    _ => unreachable!(),
+                },
+                ("schema", 3 | 4) => {
+                    // categories listed in place can be pointed at one by one, otherwise the whole list is
+                    let category_spans: Option<Vec<Span>> = match &args[2] {
+                        ListU(xs, _) => Some(xs.iter().map(ExprU::span).collect()),
+                        _ => None,
+                    };
+                    match &typecheck_all(args, env)?[..] {
+                        [StringT(delim), StringT(empty), categories @ ListT(xs), constraints @ ..]
+                            if fits(&categories_t(), &type_of(categories))
+                                && constraints
+                                    .iter()
+                                    .all(|c| fits(&constraints_t(), &type_of(c))) =>
+                        {
+                            let mut conditions = vec![];
+                            let (categories, keywords): (Vec<_>, _) = xs
+                                .iter()
                                 .map(|x| match x {
-                                    CategoryT(c) => c,
+                                    CategoryT(cat, kws, when) => {
+                                        conditions.push(when.clone());
+                                        (
+                                            (
+                                                cat.clone(),
+                                                kws.iter().map(|(kw, _)| kw.clone()).collect(),
+                                            ),
+                                            kws.iter().map(|(_, span)| *span).collect(),
+                                        )
+                                    }
                                     _ => panic!("unreachable"),
                                 })
-                                .collect();
-                            Ok(SchemaT(Schema {
-                                delim: delim.clone(),
-                                empty: empty.clone(),
-                                categories,
-                            }))
-                        } else {
-                            Err(TypeMismatch {
-                                expected: Type::List(Box::new(Type::Category)),
-                                got: Type::List(telem),
-                            })
+                                .unzip();
+                            let constraints = match constraints {
+                                [ListT(cs)] => cs.clone(),
+                                _ => vec![],
+                            };
+                            let constraints =
+                                resolve_constraints(&categories, conditions, constraints)?;
+                            let category_spans =
+                                category_spans.unwrap_or_else(|| vec![spans[2]; categories.len()]);
+                            Ok(SchemaT(
+                                Schema {
+                                    delim: delim.clone(),
+                                    empty: empty.clone(),
+                                    categories,
+                                    constraints,
+                                },
+                                SchemaSpans {
+                                    delim: spans[0],
+                                    empty: spans[1],
+                                    categories: category_spans,
+                                    keywords,
+                                },
+                            ))
+                        }
+                        [delim, empty, categories, constraints @ ..] => {
+                            let mut expected = vec![
+                                (Type::String, delim, spans[0]),
+                                (Type::String, empty, spans[1]),
+                                (categories_t(), categories, spans[2]),
+                            ];
+                            if let [constraints] = constraints {
+                                expected.push((constraints_t(), constraints, spans[3]));
+                            }
+                            Err(mismatches(&expected))
                         }
+                        _ => unreachable!(),
                     }
-                    _ => Err(TypeMismatch {
-                        expected: Type::List(Box::new(Type::Category)),
-                        got: t,
-                    }),
+                }
+                (name, _) => {
+                    // arguments with errors of their own are reported too, and shown as unknown here
+                    let mut errors = vec![];
+                    let arg_types = args
+                        .into_iter()
+                        .map(|x| match typecheck_(x, env) {
+                            Ok(x) => type_of(&x),
+                            Err(es) => {
+                                errors.extend(es);
+                                Type::Hole
+                            }
+                        })
+                        .collect();
+                    errors.push(
+                        UnknownFunction {
+                            name: name.to_string(),
+                            arg_types,
+                        }
+                        .at(span),
+                    );
+                    Err(errors)
                 }
             }
-            (name, args) => {
-                let arg_types = args
-                    .iter()
-                    .map(|x| typecheck_(x.clone()).map(|x| type_of(&x)))
-                    .collect::<Result<Vec<Type>>>()?;
-                Err(UnknownFunction {
-                    name: name.to_string(),
-                    arg_types,
-                })
+        }
+    }
+}
+
+/// typechecks every expression, keeping the errors from all of them instead of stopping at the first
+fn typecheck_all(exprs: Vec<ExprU>, env: &Env) -> Result<Vec<ExprT>> {
+    let mut xs = Vec::with_capacity(exprs.len());
+    let mut errors = vec![];
+    // errors that were already reported leave this empty, but still fail
+    let mut failed = false;
+    for expr in exprs {
+        match typecheck_(expr, env) {
+            Ok(x) => xs.push(x),
+            Err(es) => {
+                failed = true;
+                errors.extend(es)
             }
+        }
+    }
+    if !failed {
+        Ok(xs)
+    } else {
+        Err(errors)
+    }
+}
+
+/// makes a list out of the elements, as long as they're all the same type
+fn homogeneous(xs: Vec<ExprT>, span: Span) -> Result<ExprT> {
+    let mut types = HashSet::with_capacity(2);
+    for x in &xs {
+        types.insert(type_of(x));
+    }
+    let types = types.into_iter().collect::<Vec<Type>>();
+    match &types[..] {
+        // empty list can take on any type
+        [] => Ok(ListT(xs)),
+        // homogeneous
+        [_] => Ok(ListT(xs)),
+        // heterogenous
+        _ => Err(vec![HeterogeneousList(types).at(span)]),
+    }
+}
+
+/// a type mismatch for each argument that isn't the expected type
+fn mismatches(args: &[(Type, &ExprT, Span)]) -> Vec<Spanned<SchemaTypeCheckError>> {
+    args.iter()
+        .filter(|(expected, got, _)| !fits(expected, &type_of(got)))
+        .map(|(expected, got, span)| {
+            TypeMismatch {
+                expected: expected.clone(),
+                got: type_of(got),
+            }
+            .at(*span)
+        })
+        .collect()
+}
+
+/// whether a value of type `got` can be used where `expected` is. Empty lists fit any list.
+fn fits(expected: &Type, got: &Type) -> bool {
+    match (expected, got) {
+        (Type::List(e), Type::List(g)) => fits(e, g),
+        (Type::Hole, _) | (_, Type::Hole) => true,
+        (e, g) => e == g,
+    }
+}
+
+/// finds the keywords that constraints and the conditions on categories refer to
+fn resolve_constraints(
+    categories: &[(Category, Vec<Keyword>)],
+    conditions: Vec<Vec<(Keyword, Span)>>,
+    constraints: Vec<ExprT>,
+) -> Result<Vec<Constraint>> {
+    let mut resolved = vec![];
+    let mut errors = vec![];
+    for (i, when) in conditions.into_iter().enumerate() {
+        for (kw, span) in when {
+            match find_keyword(categories, &kw, span) {
+                Ok(at) if at.category == i => {
+                    errors.push(ConditionInOwnCategory(categories[i].0.name.clone()).at(span))
+                }
+                Ok(at) => resolved.push(Constraint::When(at, i)),
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+    for constraint in constraints {
+        let ConstraintT(kind, [(a, a_span), (b, b_span)]) = constraint else {
+            panic!("unreachable")
+        };
+        match (
+            find_keyword(categories, &a, a_span),
+            find_keyword(categories, &b, b_span),
+        ) {
+            (Ok(a), Ok(b)) => resolved.push(match kind {
+                ConstraintKind::Requires => Constraint::Requires(a, b),
+                ConstraintKind::Excludes => Constraint::Excludes(a, b),
+            }),
+            (a, b) => errors.extend(a.err().into_iter().chain(b.err())),
+        }
+    }
+    if errors.is_empty() {
+        Ok(resolved)
+    } else {
+        Err(errors)
+    }
+}
+
+/// looks up a keyword by its id, or by its name when only one keyword has it
+fn find_keyword(
+    categories: &[(Category, Vec<Keyword>)],
+    kw: &Keyword,
+    span: Span,
+) -> StdResult<KeywordRef, Spanned<SchemaTypeCheckError>> {
+    let all = || {
+        categories
+            .iter()
+            .enumerate()
+            .flat_map(|(category, (_, kws))| {
+                kws.iter()
+                    .enumerate()
+                    .map(move |(keyword, x)| (KeywordRef { category, keyword }, x))
+            })
+    };
+    if let Some((at, _)) = all().find(|(_, x)| x.id == kw.id) {
+        return Ok(at);
+    }
+    let named: Vec<KeywordRef> = all()
+        .filter(|(_, x)| x.name == kw.name)
+        .map(|(at, _)| at)
+        .collect();
+    match named[..] {
+        [at] => Ok(at),
+        [] => Err(UnknownKeyword(kw.name.clone()).at(span)),
+        _ => Err(AmbiguousKeyword(kw.name.clone()).at(span)),
+    }
+}
+
+/// a category that takes a value, which can be left out
+fn value_category(name: &str, value: ValueKind) -> ExprT {
+    CategoryT(
+        Category {
+            name: name.to_string(),
+            requirement: AtMost(1),
+            value: Some(value),
+            parents: vec![],
+            former_ids: vec![],
         },
+        vec![],
+        vec![],
+    )
+}
+
+/// lists nested keywords after the keyword they're under, keeping track of which one that is
+fn flatten(
+    xs: &[ExprT],
+    parent: Option<usize>,
+    keywords: &mut Vec<(Keyword, Span)>,
+    category: &mut Category,
+) {
+    for x in xs {
+        let KeywordT {
+            keyword,
+            span,
+            former_ids,
+            children,
+        } = x
+        else {
+            panic!("unreachable")
+        };
+        let i = keywords.len();
+        keywords.push((keyword.clone(), *span));
+        category.parents.push(parent);
+        category.former_ids.push(former_ids.clone());
+        flatten(children, Some(i), keywords, category);
     }
 }
 
+fn keywords_t() -> Type {
+    Type::List(Box::new(Type::Keyword))
+}
+
+fn categories_t() -> Type {
+    Type::List(Box::new(Type::Category))
+}
+
+fn constraints_t() -> Type {
+    Type::List(Box::new(Type::Constraint))
+}
+
 fn type_of(expr: &ExprT) -> Type {
     match expr {
-        SchemaT(_) => Type::Schema,
+        SchemaT(..) => Type::Schema,
         RequirementT(_) => Type::Requirement,
-        CategoryT(_) => Type::Category,
+        MaxLenT(_) => Type::TextLimit,
+        ConstraintT(..) => Type::Constraint,
+        CategoryT(..) => Type::Category,
         NatT(_) => Type::Nat,
         StringT(_) => Type::String,
-        KeywordT(_) => Type::Keyword,
+        KeywordT { .. } => Type::Keyword,
         ListT(args) => match &args[..] {
             [] => Type::List(Box::new(Type::Hole)),
             [h, _t @ ..] => Type::List(Box::new(type_of(h))),
@@ -181,15 +654,249 @@ fn type_of(expr: &ExprT) -> Type {
 
 #[test]
 fn test_typecheck() {
-    let hetero_list = typecheck_(ListU(vec![
-        StringU("a".to_string()),
-        KeywordU {
-            name: "boo".to_string(),
-            id: "b".to_string(),
-        },
-    ]));
-    match hetero_list {
-        Err(HeterogeneousList(t)) if t.len() == 2 => (),
+    let hetero_list = typecheck_(
+        ListU(
+            vec![
+                StringU("a".to_string(), Span::default()),
+                KeywordU {
+                    name: "boo".to_string(),
+                    id: "b".to_string(),
+                    span: Span::default(),
+                },
+            ],
+            Span::default(),
+        ),
+        &vec![],
+    );
+    match hetero_list.as_ref().map_err(Vec::as_slice) {
+        Err(
+            [Spanned {
+                value: HeterogeneousList(t),
+                ..
+            }],
+        ) if t.len() == 2 => (),
         _ => panic!("heterogeneous lists are not allowed."),
     }
 }
+
+#[test]
+fn typecheck_error_spans() {
+    use super::{parse::parse, Location};
+    let location = |input: &str| {
+        let e = typecheck(parse(input).unwrap()).unwrap_err();
+        let location = Location::of_span(input, e[0].span);
+        (location.line, location.column, location.width)
+    };
+    assert_eq!(
+        location("schema \"-\" \"_\"\n  [ category \"Media\" (exactly_one 1) ['art']\n  ]"),
+        (2, 23, 13)
+    );
+    assert_eq!(
+        location(
+            "schema \"-\" \"_\"\n  [ category \"Media\" (exactly 1) ['art', 'photo'/'_']\n  ]"
+        ),
+        (2, 42, 11)
+    );
+    assert_eq!(location("schema \"-\" \"_\" [1]"), (1, 16, 3));
+}
+
+#[test]
+fn typecheck_let() {
+    use super::{parse::parse, Location};
+    let check = |input: &str| typecheck(parse(input).unwrap());
+    let errors = |input: &str| {
+        check(input)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| {
+                let location = Location::of_span(input, e.span);
+                (e.value, location.line, location.column)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let schema = check(
+        "let family = ['alice', 'bob'] in\nlet friends = ['carol'/'c'] in\nschema \"-\" \"_\" [ category \"People\" (at_least 0) (family ++ friends) ]",
+    )
+    .unwrap();
+    let ids: Vec<&str> = schema.categories[0]
+        .1
+        .iter()
+        .map(|kw| kw.id.as_str())
+        .collect();
+    assert_eq!(ids, vec!["alice", "bob", "c"]);
+
+    // the innermost binding wins, and any kind of value can be bound
+    let schema = check(
+        "let n = 1 in\nlet n = 2 in\nlet any = at_least n in\nschema \"-\" \"_\" [ category \"People\" (any) ['a'] ]",
+    )
+    .unwrap();
+    assert_eq!(schema.categories[0].0.requirement, AtLeast(2));
+
+    // names are only bound in the body
+    assert_eq!(
+        errors("let xs = xs in\nschema \"-\" \"_\" [ category \"A\" (exactly 1) ys ]"),
+        vec![
+            (UnboundVariable("xs".to_string()), 1, 10),
+            (UnboundVariable("ys".to_string()), 2, 43),
+        ]
+    );
+    // a binding with errors isn't reported again where it's used
+    assert_eq!(
+        errors("let xs = ['a', \"b\"] in\nschema \"-\" \"_\" [ category \"A\" (exactly 1) xs ]")
+            .len(),
+        1
+    );
+    assert_eq!(
+        errors("schema \"-\" \"_\" [ category \"A\" (exactly 1) (['a'] ++ 1) ]"),
+        vec![(
+            TypeMismatch {
+                expected: Type::List(Box::new(Type::Hole)),
+                got: Type::Nat
+            },
+            1,
+            53
+        )]
+    );
+    // ids are still checked where they were written
+    assert_eq!(
+        errors("let xs = ['a'] in\nschema \"-\" \"_\" [ category \"A\" (exactly 1) xs, category \"B\" (exactly 1) xs ]")[0].1,
+        1
+    );
+}
+
+#[test]
+fn typecheck_between() {
+    use super::parse::parse;
+    let check = |req: &str| {
+        let input = format!("schema \"-\" \"_\" [ category \"People\" ({req}) ['a', 'b'] ]");
+        typecheck(parse(&input).unwrap())
+            .map(|schema| schema.categories[0].0.requirement)
+            .map_err(|mut es| es.remove(0).value)
+    };
+    assert_eq!(check("between 1 3"), Ok(Between(1, 3)));
+    assert_eq!(check("between 2 2"), Ok(Between(2, 2)));
+    assert_eq!(check("between 3 1"), Err(EmptyRange { min: 3, max: 1 }));
+    assert!(matches!(check("between 1 'a'"), Err(TypeMismatch { .. })));
+    assert!(matches!(check("between 1"), Err(UnknownFunction { .. })));
+    assert_eq!(Between(1, 3).to_string(), "between 1 and 3");
+    assert!(Between(1, 3).allows(3) && !Between(1, 3).allows(0) && !Between(1, 3).allows(4));
+}
+
+#[test]
+fn typecheck_constraints() {
+    use super::parse::parse;
+    let check = |input: &str| typecheck(parse(input).unwrap()).map_err(|mut es| es.remove(0).value);
+    let at = |category, keyword| KeywordRef { category, keyword };
+    let schema = check(
+        "schema \"-\" \"_\"\n  [ category \"Media\" (exactly 1) ['photo'/'ph', 'video'/'v']\n  , when 'v' (category \"Duration\" (exactly 1) ['short', 'long'])\n  ]\n  [ requires 'long' 'video', excludes 'short' 'photo' ]",
+    )
+    .unwrap();
+    assert_eq!(
+        schema.constraints,
+        vec![
+            Constraint::When(at(0, 1), 1),
+            Constraint::Requires(at(1, 1), at(0, 1)),
+            Constraint::Excludes(at(1, 0), at(0, 0)),
+        ]
+    );
+    assert_eq!(schema.conditions(1), vec![at(0, 1)]);
+
+    assert_eq!(
+        check("schema \"-\" \"_\" [ category \"A\" (exactly 1) ['a'] ] [ requires 'a' 'b' ]"),
+        Err(UnknownKeyword("b".to_string()))
+    );
+    assert_eq!(
+        check("schema \"-\" \"_\" [ category \"A\" (exactly 1) ['a'/'a1'], category \"B\" (exactly 1) ['a'/'a2'] ] [ excludes 'a' 'a1' ]"),
+        Err(AmbiguousKeyword("a".to_string()))
+    );
+    assert_eq!(
+        check("schema \"-\" \"_\" [ when 'a' (category \"A\" (exactly 1) ['a']) ]"),
+        Err(ConditionInOwnCategory("A".to_string()))
+    );
+    assert!(matches!(
+        check("schema \"-\" \"_\" [ category \"A\" (exactly 1) ['a'] ] ['a']"),
+        Err(TypeMismatch { .. })
+    ));
+}
+
+#[test]
+fn typecheck_values() {
+    use super::{analysis, parse::parse};
+    let check = |category: &str| {
+        let input = format!("schema \"-\" \"_\" [ {category} ]");
+        typecheck(parse(&input).unwrap())
+            .map(|schema| schema.categories[0].0.clone())
+            .map_err(|mut es| es.remove(0).value)
+    };
+    let taken = check("date \"Taken\" \"%Y%m%d\"").unwrap();
+    assert_eq!(taken.value, Some(ValueKind::Date("%Y%m%d".to_string())));
+    assert_eq!(taken.requirement, AtMost(1));
+    assert_eq!(
+        check("number \"Rating\" 1 5").unwrap().value,
+        Some(ValueKind::Number(1, 5))
+    );
+    assert_eq!(
+        check("text \"Caption\" (max_len 20)").unwrap().value,
+        Some(ValueKind::Text(20))
+    );
+
+    // formats have to be read back to the same date
+    assert_eq!(
+        check("date \"Taken\" \"%Y%m\""),
+        Err(InvalidDateFormat("%Y%m".to_string()))
+    );
+    assert!(matches!(
+        check("date \"Taken\" \"%Q\""),
+        Err(InvalidDateFormat(_))
+    ));
+    assert_eq!(
+        check("date \"Taken\" \"%Y-%m-%d\""),
+        Err(DelimiterInValue("Taken".to_string()))
+    );
+    assert_eq!(
+        check("number \"Rating\" 5 1"),
+        Err(EmptyRange { min: 5, max: 1 })
+    );
+    assert!(matches!(
+        check("text \"Caption\" 20"),
+        Err(TypeMismatch { .. })
+    ));
+
+    let schema = typecheck(
+        parse("schema \"-\" \"_\" [ date \"Taken\" \"%d %B %Y\", number \"Rating\" 1 10, text \"Caption\" (max_len 5) ]")
+            .unwrap(),
+    )
+    .unwrap();
+    // the longest month name, the longest number, and text of 4 byte characters
+    let length = analysis::max_name_length(&schema, 0);
+    assert_eq!(length.chars, 6 + 1 + 17 + 1 + 2 + 1 + 5);
+    assert_eq!(length.bytes, 6 + 1 + 17 + 1 + 2 + 1 + 20);
+}
+
+#[test]
+fn typecheck_nested() {
+    use super::parse::parse;
+    let check = |keywords: &str| {
+        let input = format!("schema \"-\" \"_\" [ category \"Subject\" (at_least 1) {keywords} ]");
+        typecheck(parse(&input).unwrap()).map_err(|mut es| es.remove(0).value)
+    };
+    let schema = check("['animal' ['dog', 'bird' ['owl']], 'plant']").unwrap();
+    let (category, keywords) = &schema.categories[0];
+    let names: Vec<&str> = keywords.iter().map(|kw| kw.name.as_str()).collect();
+    assert_eq!(names, vec!["animal", "dog", "bird", "owl", "plant"]);
+    assert_eq!(
+        category.parents,
+        vec![None, Some(0), Some(0), Some(2), None]
+    );
+    assert!(category.is_under(3, 0) && !category.is_under(0, 3) && !category.is_under(4, 0));
+
+    // nested keywords can be named, and ids are unique across every level
+    let schema = check("(let dog = 'dog' ['puppy'] in ['animal' [dog, 'cat']])").unwrap();
+    assert_eq!(
+        schema.categories[0].0.parents,
+        vec![None, Some(0), Some(1), Some(0)]
+    );
+    assert!(check("['animal' ['dog'/'d'], 'dish'/'d']").is_err());
+    assert!(matches!(check("['animal' [1]]"), Err(TypeMismatch { .. })));
+}